@@ -1,8 +1,10 @@
-#![feature(hash_drain_filter)]
 use collect_array::CollectArrayResult;
 use itertools::Itertools;
 use std::collections::{hash_map::Entry, HashMap};
 
+mod sim;
+use sim::BingoSimulator;
+
 pub struct BoardLayout {
     pos_to_num: [[u32; Self::USIZE]; Self::USIZE],
     num_to_pos: HashMap<u32, (u8, u8)>,
@@ -77,6 +79,15 @@ impl BoardState {
         has_won
     }
 
+    pub fn layout(&self) -> &BoardLayout {
+        &self.layout
+    }
+
+    pub fn reset(&mut self) {
+        self.horiz = Default::default();
+        self.vert = Default::default();
+    }
+
     pub fn has_won(&self) -> bool {
         self.horiz.contains(&Self::WIN_VALUE) || self.vert.contains(&Self::WIN_VALUE)
     }
@@ -128,22 +139,22 @@ impl From<BoardLayout> for BoardState {
     }
 }
 
-fn run(input: &str) {
+fn parse_input(input: &str) -> (Vec<u32>, Vec<BoardLayout>) {
     let mut lines = input.lines();
 
     let rand_numbers = lines
         .next()
         .unwrap()
         .split(',')
-        .map(|n| n.parse::<u32>().unwrap());
+        .map(|n| n.parse::<u32>().unwrap())
+        .collect();
 
-    let mut boards: HashMap<usize, BoardState> = lines
+    let layouts = lines
         .chunks(6)
         .into_iter()
-        .enumerate()
-        .map(|(i, mut chunk)| {
+        .map(|mut chunk| {
             assert_eq!(chunk.next(), Some(""));
-            let layout: BoardLayout = chunk
+            chunk
                 .map(|r| {
                     r.split_ascii_whitespace()
                         .map(|n| n.parse().unwrap())
@@ -152,11 +163,22 @@ fn run(input: &str) {
                 })
                 .collect::<CollectArrayResult<_, { BoardLayout::USIZE }>>()
                 .unwrap()
-                .into();
-            (i, layout.into())
+                .into()
         })
         .collect();
 
+    (rand_numbers, layouts)
+}
+
+fn run(input: &str) {
+    let (rand_numbers, layouts) = parse_input(input);
+
+    let mut boards: HashMap<usize, BoardState> = layouts
+        .into_iter()
+        .map(BoardState::from)
+        .enumerate()
+        .collect();
+
     for number in rand_numbers {
         if boards.is_empty() {
             println!("All done.");
@@ -164,7 +186,7 @@ fn run(input: &str) {
         }
         println!("Running {}...", number);
         boards
-            .drain_filter(|_, board| board.mark_number(number))
+            .extract_if(|_, board| board.mark_number(number))
             .sorted_unstable_by_key(|(i, _)| *i)
             .for_each(|(i, board)| {
                 let sum = board.unmarked_sum();
@@ -180,6 +202,111 @@ fn run(input: &str) {
     }
 }
 
+fn simulate(input: &str, trials: usize) {
+    let (rand_numbers, layouts) = parse_input(input);
+    let mut simulator = BingoSimulator::new(layouts, &rand_numbers, 2021);
+    let report = simulator.run(trials);
+
+    // Only the extremes are shown, as there's a line per board otherwise
+    let favourite = (0..simulator.num_boards())
+        .max_by(|&a, &b| {
+            report
+                .first_win_probability(a)
+                .total_cmp(&report.first_win_probability(b))
+        })
+        .unwrap();
+    println!(
+        "Over {} shuffled draw orders, board #{} wins first most often ({:.2}%, expected draws {:.2}, exact {:.2})",
+        trials,
+        favourite,
+        report.first_win_probability(favourite) * 100.0,
+        report.boards[favourite]
+            .expected_draws_to_win()
+            .unwrap_or(f64::NAN),
+        simulator
+            .analyze_exact(favourite)
+            .expected_draws_to_win()
+            .unwrap_or(f64::NAN),
+    );
+    if let Some(best) = report.best_for_squid() {
+        println!(
+            "Let the squid win with board #{} ({:.2}% chance of winning last)",
+            best,
+            report.last_win_probability(best) * 100.0,
+        );
+    }
+}
+
 pub fn main() {
     run(include_str!("input.txt"));
+    simulate(include_str!("input.txt"), 100);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sim::analyze_exact;
+
+    fn layout() -> BoardLayout {
+        let mut nums = [[0; BoardLayout::USIZE]; BoardLayout::USIZE];
+        for (i, n) in nums.iter_mut().flatten().enumerate() {
+            *n = i as u32 + 1;
+        }
+        BoardLayout::new(nums)
+    }
+
+    #[test]
+    fn exact_matches_brute_force() {
+        // The top row, the rest of the left column, a stray number from the
+        // board and one that isn't on it
+        let pool = [1, 2, 3, 4, 5, 6, 11, 16, 21, 13, 99];
+        let exact = analyze_exact(&layout(), &pool);
+        assert_eq!(exact.num_draws(), pool.len());
+
+        for draws in 0..=pool.len() {
+            let mut wins = 0;
+            let mut total = 0;
+            for drawn in pool.iter().combinations(draws) {
+                let mut board = BoardState::new(layout());
+                drawn.into_iter().for_each(|&n| {
+                    board.mark_number(n);
+                });
+                wins += board.has_won() as u32;
+                total += 1;
+            }
+            let brute_force = wins as f64 / total as f64;
+            assert!((exact.win_by_draw(draws) - brute_force).abs() < 1e-9);
+        }
+
+        let total: f64 = (0..=pool.len() + 1).map(|d| exact.win_on_draw(d)).sum();
+        assert!((total - exact.win_probability()).abs() < 1e-9);
+        assert!((exact.win_probability() - 1.0).abs() < 1e-9);
+
+        // Without the 1 neither line can be completed
+        let exact = analyze_exact(&layout(), &pool[1..]);
+        assert_eq!(exact.win_probability(), 0.0);
+        assert_eq!(exact.expected_draws_to_win(), None);
+    }
+
+    #[test]
+    fn simulation_is_reproducible() {
+        let (rand_numbers, _) = parse_input(include_str!("test.txt"));
+        let simulate = |seed| {
+            let (_, layouts) = parse_input(include_str!("test.txt"));
+            BingoSimulator::new(layouts, &rand_numbers, seed).run(2000)
+        };
+        let report = simulate(7);
+        assert_eq!(report, simulate(7));
+        assert_ne!(report, simulate(8));
+
+        let (_, layouts) = parse_input(include_str!("test.txt"));
+        let simulator = BingoSimulator::new(layouts, &rand_numbers, 7);
+        for (i, stats) in report.boards.iter().enumerate() {
+            let exact = simulator.analyze_exact(i).expected_draws_to_win().unwrap();
+            let simulated = stats.expected_draws_to_win().unwrap();
+            assert!((simulated - exact).abs() / exact < 0.05);
+        }
+        let first_wins: usize = report.boards.iter().map(|b| b.first_wins).sum();
+        assert!(first_wins >= report.trials);
+    }
 }
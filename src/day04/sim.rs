@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::{BoardLayout, BoardState};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Xorshift64(u64);
+
+impl Xorshift64 {
+    pub const fn new(seed: u64) -> Self {
+        // An all-zero state would only ever produce zeroes
+        Xorshift64(if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoardStats {
+    pub first_wins: usize,
+    pub last_wins: usize,
+    pub completions: usize,
+    pub total_draws_to_win: u64,
+    pub scores: BTreeMap<u32, usize>,
+}
+
+impl BoardStats {
+    pub fn expected_draws_to_win(&self) -> Option<f64> {
+        if self.completions == 0 {
+            None
+        } else {
+            Some(self.total_draws_to_win as f64 / self.completions as f64)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimulationReport {
+    pub trials: usize,
+    pub boards: Vec<BoardStats>,
+}
+
+impl SimulationReport {
+    pub fn first_win_probability(&self, board: usize) -> f64 {
+        self.boards[board].first_wins as f64 / self.trials as f64
+    }
+
+    pub fn last_win_probability(&self, board: usize) -> f64 {
+        self.boards[board].last_wins as f64 / self.trials as f64
+    }
+
+    pub fn best_for_squid(&self) -> Option<usize> {
+        (0..self.boards.len()).max_by(|&a, &b| {
+            self.boards[a]
+                .last_wins
+                .cmp(&self.boards[b].last_wins)
+                .then(b.cmp(&a))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExactAnalysis {
+    win_by_draw: Vec<f64>,
+}
+
+impl ExactAnalysis {
+    pub fn num_draws(&self) -> usize {
+        self.win_by_draw.len() - 1
+    }
+
+    pub fn win_by_draw(&self, draw: usize) -> f64 {
+        self.win_by_draw[draw.min(self.num_draws())]
+    }
+
+    pub fn win_on_draw(&self, draw: usize) -> f64 {
+        if draw == 0 || draw > self.num_draws() {
+            0.0
+        } else {
+            self.win_by_draw[draw] - self.win_by_draw[draw - 1]
+        }
+    }
+
+    pub fn win_probability(&self) -> f64 {
+        self.win_by_draw(self.num_draws())
+    }
+
+    pub fn expected_draws_to_win(&self) -> Option<f64> {
+        let p_win = self.win_probability();
+        if p_win <= 0.0 {
+            return None;
+        }
+        let total: f64 = (1..=self.num_draws())
+            .map(|draw| draw as f64 * self.win_on_draw(draw))
+            .sum();
+        Some(total / p_win)
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    let k = k.min(n - k);
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+pub fn analyze_exact(layout: &BoardLayout, pool: &[u32]) -> ExactAnalysis {
+    let pool_set: HashSet<u32> = pool.iter().copied().collect();
+    let num_draws = pool_set.len();
+
+    let mut markable = 0u32;
+    let mut num_markable = 0;
+    for y in 0..BoardLayout::SIZE {
+        for x in 0..BoardLayout::SIZE {
+            if pool_set.contains(&layout.get_num_at(x, y)) {
+                markable |= 1 << (y * BoardLayout::SIZE + x);
+                num_markable += 1;
+            }
+        }
+    }
+
+    let row_mask = (1u32 << BoardLayout::SIZE) - 1;
+    let col_mask = (0..BoardLayout::SIZE).fold(0u32, |m, y| m | (1 << (y * BoardLayout::SIZE)));
+    let lines: Vec<u32> = (0..BoardLayout::SIZE)
+        .flat_map(|i| [row_mask << (i * BoardLayout::SIZE), col_mask << i])
+        .filter(|&line| line & markable == line)
+        .collect();
+
+    // Number of ways to choose `m` marked cells such that at least one line is
+    // complete, by inclusion-exclusion over the set of completed lines
+    let mut winning_subsets = vec![0.0; num_markable + 1];
+    for subset in 1usize..(1 << lines.len()) {
+        let union = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| subset & (1 << i) != 0)
+            .fold(0u32, |u, (_, line)| u | line);
+        let union_len = union.count_ones() as usize;
+        let sign = if subset.count_ones() % 2 == 1 {
+            1.0
+        } else {
+            -1.0
+        };
+        for (m, count) in winning_subsets.iter_mut().enumerate().skip(union_len) {
+            *count += sign * binomial(num_markable - union_len, m - union_len);
+        }
+    }
+
+    let win_by_draw = (0..=num_draws)
+        .map(|draws| {
+            let total = binomial(num_draws, draws);
+            let p: f64 = winning_subsets
+                .iter()
+                .enumerate()
+                .take(draws + 1)
+                .map(|(m, count)| count * binomial(num_draws - num_markable, draws - m))
+                .sum();
+            (p / total).clamp(0.0, 1.0)
+        })
+        .collect();
+
+    ExactAnalysis { win_by_draw }
+}
+
+pub struct BingoSimulator {
+    boards: Vec<BoardState>,
+    pool: Vec<u32>,
+    rng: Xorshift64,
+}
+
+impl BingoSimulator {
+    pub fn new<I>(layouts: I, pool: &[u32], seed: u64) -> Self
+    where
+        I: IntoIterator<Item = BoardLayout>,
+    {
+        // Sorted so that runs with the same seed are reproducible
+        let mut pool = pool.to_vec();
+        pool.sort_unstable();
+        pool.dedup();
+        BingoSimulator {
+            boards: layouts.into_iter().map(BoardState::new).collect(),
+            pool,
+            rng: Xorshift64::new(seed),
+        }
+    }
+
+    pub fn num_boards(&self) -> usize {
+        self.boards.len()
+    }
+
+    pub fn analyze_exact(&self, board: usize) -> ExactAnalysis {
+        analyze_exact(self.boards[board].layout(), &self.pool)
+    }
+
+    pub fn run(&mut self, trials: usize) -> SimulationReport {
+        let mut report = SimulationReport {
+            trials,
+            boards: vec![BoardStats::default(); self.boards.len()],
+        };
+        let mut order = self.pool.clone();
+        let mut won_at = vec![None; self.boards.len()];
+
+        for _ in 0..trials {
+            self.rng.shuffle(&mut order);
+            self.boards.iter_mut().for_each(BoardState::reset);
+            won_at.iter_mut().for_each(|w| *w = None);
+
+            let mut remaining = self.boards.len();
+            for (draw, &number) in order.iter().enumerate() {
+                if remaining == 0 {
+                    break;
+                }
+                for (i, board) in self.boards.iter_mut().enumerate() {
+                    if won_at[i].is_none() && board.mark_number(number) {
+                        won_at[i] = Some(draw + 1);
+                        remaining -= 1;
+                        let stats = &mut report.boards[i];
+                        stats.completions += 1;
+                        stats.total_draws_to_win += (draw + 1) as u64;
+                        *stats
+                            .scores
+                            .entry(board.unmarked_sum() * number)
+                            .or_default() += 1;
+                    }
+                }
+            }
+
+            let first = won_at.iter().flatten().min();
+            let last = won_at.iter().flatten().max();
+            for (i, &draw) in won_at.iter().enumerate() {
+                if draw.is_some() && draw == first.copied() {
+                    report.boards[i].first_wins += 1;
+                }
+                if remaining == 0 && draw == last.copied() {
+                    report.boards[i].last_wins += 1;
+                }
+            }
+        }
+
+        report
+    }
+}
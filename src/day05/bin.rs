@@ -1,6 +1,6 @@
 #![feature(exclusive_range_pattern)]
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fmt::Display;
 use std::iter::{FromIterator, FusedIterator};
@@ -9,6 +9,9 @@ use std::str::FromStr;
 
 use itertools::Itertools;
 
+mod sweep;
+use sweep::SweepMap;
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
 struct Point {
     pub x: i32,
@@ -43,10 +46,35 @@ impl FromStr for Point {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum LineKind {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+    Sloped,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
 struct LineDef(pub Point, pub Point);
 
 impl LineDef {
+    pub const fn kind(&self) -> LineKind {
+        let dx = self.1.x as i64 - self.0.x as i64;
+        let dy = self.1.y as i64 - self.0.y as i64;
+        if dy == 0 {
+            LineKind::Horizontal
+        } else if dx == 0 {
+            LineKind::Vertical
+        } else if dx == dy {
+            LineKind::Diagonal
+        } else if dx == -dy {
+            LineKind::AntiDiagonal
+        } else {
+            LineKind::Sloped
+        }
+    }
+
     pub const fn is_horiz(&self) -> bool {
         self.0.y == self.1.y
     }
@@ -60,13 +88,32 @@ impl LineDef {
     }
 
     pub fn points(&self) -> LineDefIter {
+        let dx = self.1.x as i64 - self.0.x as i64;
+        let dy = self.1.y as i64 - self.0.y as i64;
+        let steps = gcd(dx.unsigned_abs(), dy.unsigned_abs()) as i64;
+        // Only points where both coordinates are integers lie on the line
+        let step = if steps == 0 {
+            Point::default()
+        } else {
+            Point::new((dx / steps) as i32, (dy / steps) as i32)
+        };
         LineDefIter::Moving {
             curr: self.0,
-            end: self.1,
+            step,
+            remaining: steps as u64,
         }
     }
 }
 
+const fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
 impl FromStr for LineDef {
     type Err = ParseError;
 
@@ -80,7 +127,11 @@ impl FromStr for LineDef {
 }
 
 enum LineDefIter {
-    Moving { curr: Point, end: Point },
+    Moving {
+        curr: Point,
+        step: Point,
+        remaining: u64,
+    },
     Done,
 }
 
@@ -88,15 +139,19 @@ impl Iterator for LineDefIter {
     type Item = Point;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Self::Moving { curr, end } = self {
+        if let Self::Moving {
+            curr,
+            step,
+            remaining,
+        } = self
+        {
             let result = *curr;
-            let x_diff = end.x.cmp(&curr.x);
-            let y_diff = end.y.cmp(&curr.y);
-            if x_diff.is_eq() && y_diff.is_eq() {
+            if *remaining == 0 {
                 *self = Self::Done;
             } else {
-                curr.x += x_diff as i32;
-                curr.y += y_diff as i32;
+                curr.x += step.x;
+                curr.y += step.y;
+                *remaining -= 1;
             }
             Some(result)
         } else {
@@ -137,11 +192,14 @@ impl From<ParseIntError> for ParseError {
     }
 }
 
+type LineId = usize;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct LineMap {
     min: Point,
     max: Point,
-    filled: HashMap<Point, u32>,
+    lines: Vec<LineDef>,
+    filled: HashMap<Point, Vec<LineId>>,
 }
 
 impl LineMap {
@@ -149,22 +207,65 @@ impl LineMap {
         LineMap {
             min: Point::MAX,
             max: Point::MIN,
+            lines: Vec::new(),
             filled: HashMap::new(),
         }
     }
 
-    pub fn add_line(&mut self, line: &LineDef) {
+    pub fn add_line(&mut self, line: &LineDef) -> LineId {
+        let id = self.lines.len();
         self.min.x = self.min.x.min(line.0.x).min(line.1.x);
         self.max.x = self.max.x.max(line.0.x).max(line.1.x);
         self.min.y = self.min.y.min(line.0.y).min(line.1.y);
         self.max.y = self.max.y.max(line.0.y).max(line.1.y);
         for point in line.points() {
-            *self.filled.entry(point).or_default() += 1;
+            self.filled.entry(point).or_default().push(id);
         }
+        self.lines.push(line.clone());
+        id
+    }
+
+    pub fn line(&self, id: LineId) -> &LineDef {
+        &self.lines[id]
+    }
+
+    pub fn count_at(&self, point: Point) -> usize {
+        self.filled.get(&point).map_or(0, Vec::len)
+    }
+
+    pub fn lines_at(&self, point: Point) -> &[LineId] {
+        self.filled.get(&point).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn points_with_overlap(
+        &self,
+        min_count: usize,
+    ) -> impl Iterator<Item = (Point, usize)> + '_ {
+        self.filled
+            .iter()
+            .map(|(p, ids)| (*p, ids.len()))
+            .filter(move |(_, c)| *c >= min_count)
     }
 
     pub fn count_overlaps(&self) -> usize {
-        self.filled.iter().filter(|(_, c)| **c > 1).count()
+        self.points_with_overlap(2).count()
+    }
+
+    pub fn histogram(&self, min: Point, max: Point) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        let area =
+            (max.x as i64 - min.x as i64 + 1).max(0) * (max.y as i64 - min.y as i64 + 1).max(0);
+        let mut covered = 0;
+        for (p, ids) in &self.filled {
+            if (min.x..=max.x).contains(&p.x) && (min.y..=max.y).contains(&p.y) {
+                *histogram.entry(ids.len()).or_default() += 1;
+                covered += 1;
+            }
+        }
+        if area > covered {
+            histogram.insert(0, (area - covered) as usize);
+        }
+        histogram
     }
 
     pub fn print(&self) {
@@ -172,10 +273,10 @@ impl LineMap {
             for x in self.min.x..=self.max.x {
                 print!(
                     "{}",
-                    match self.filled.get(&Point::new(x, y)) {
-                        Some(num @ 0..=9) => std::char::from_digit(*num, 10).unwrap(),
-                        Some(_) => '+',
-                        None => '.',
+                    match self.count_at(Point::new(x, y)) {
+                        0 => '.',
+                        num @ 1..=9 => std::char::from_digit(num as u32, 10).unwrap(),
+                        _ => '+',
                     }
                 );
             }
@@ -210,9 +311,133 @@ pub fn main() {
     let map: LineMap = input.iter().filter(|d| d.is_cardinal()).collect();
     map.print();
     println!("P1: {} overlaps", map.count_overlaps());
+    let sweep: SweepMap = input.iter().filter(|d| d.is_cardinal()).collect();
+    println!("P1 (sweep): {} overlaps", sweep.count_overlaps());
 
     // P2
     let map: LineMap = input.iter().collect();
     map.print();
     println!("P2: {} overlaps", map.count_overlaps());
+    let sweep: SweepMap = input.iter().collect();
+    println!("P2 (sweep): {} overlaps", sweep.count_overlaps());
+
+    if let Some((point, count)) = map
+        .points_with_overlap(2)
+        .max_by_key(|&(p, c)| (c, std::cmp::Reverse((p.y, p.x))))
+    {
+        println!(
+            "Most dangerous point: ({}, {}) with {} vents",
+            point.x, point.y, count
+        );
+        for &id in map.lines_at(point) {
+            let line = map.line(id);
+            println!(
+                "  #{}: {},{} -> {},{}",
+                id, line.0.x, line.0.y, line.1.x, line.1.y
+            );
+        }
+        let radius = 5;
+        let histogram = map.histogram(
+            Point::new(point.x - radius, point.y - radius),
+            Point::new(point.x + radius, point.y + radius),
+        );
+        println!("  Surrounding vent counts: {:?}", histogram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineDef, LineKind, LineMap, Point, SweepMap};
+
+    fn parse(input: &str) -> Vec<LineDef> {
+        input.lines().map(|s| s.parse().unwrap()).collect()
+    }
+
+    fn check_agrees(lines: &[LineDef]) {
+        let map: LineMap = lines.iter().collect();
+        let sweep: SweepMap = lines.iter().collect();
+        assert_eq!(sweep.count_overlaps(), map.count_overlaps());
+    }
+
+    #[test]
+    fn sloped_points_are_lattice_points() {
+        let line: LineDef = "6,3 -> 0,0".parse().unwrap();
+        assert_eq!(line.kind(), LineKind::Sloped);
+        assert_eq!(
+            line.points().collect::<Vec<_>>(),
+            [(6, 3), (4, 2), (2, 1), (0, 0)].map(|(x, y)| Point::new(x, y))
+        );
+
+        let line: LineDef = "0,0 -> 3,1".parse().unwrap();
+        assert_eq!(line.points().count(), 2);
+    }
+
+    #[test]
+    fn map_queries() {
+        let map: LineMap = parse(include_str!("test.txt")).iter().collect();
+        assert_eq!(map.count_at(Point::new(4, 4)), 3);
+        assert_eq!(map.lines_at(Point::new(4, 4)), &[1, 2, 8]);
+        assert_eq!(map.lines_at(Point::new(9, 9)), &[] as &[usize]);
+        assert_eq!(map.points_with_overlap(3).count(), 2);
+
+        let histogram = map.histogram(Point::new(0, 0), Point::new(9, 9));
+        assert_eq!(histogram.values().sum::<usize>(), 100);
+        assert_eq!(histogram[&2] + histogram[&3], 12);
+    }
+
+    #[test]
+    fn sweep_agrees_on_test() {
+        let lines = parse(include_str!("test.txt"));
+        check_agrees(&lines);
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|d| d.is_cardinal())
+                .collect::<SweepMap>()
+                .count_overlaps(),
+            5
+        );
+        assert_eq!(lines.iter().collect::<SweepMap>().count_overlaps(), 12);
+    }
+
+    #[test]
+    fn sweep_agrees_on_input() {
+        check_agrees(&parse(include_str!("input.txt")));
+    }
+
+    #[test]
+    fn sweep_handles_sloped_lines() {
+        let mut lines = parse(include_str!("test.txt"));
+        lines.extend(parse(
+            "6,3 -> 0,0\n\
+             0,0 -> 9,6\n\
+             1,8 -> 7,5\n\
+             2,2 -> 8,5\n\
+             -3,-1 -> 3,1",
+        ));
+        assert!(lines.iter().any(|l| l.kind() == LineKind::Sloped));
+        check_agrees(&lines);
+
+        // Two sloped lines sharing every other point
+        let lines = parse("0,0 -> 6,3\n2,1 -> 8,4");
+        assert_eq!(lines.iter().collect::<SweepMap>().count_overlaps(), 3);
+        check_agrees(&lines);
+    }
+
+    #[test]
+    fn sweep_handles_huge_coordinates() {
+        let lines = parse(
+            "0,0 -> 4000000,4000000\n\
+             0,4000000 -> 4000000,0\n\
+             1000000,0 -> 1000000,3000000\n\
+             1000000,2000000 -> 1000000,4000000\n\
+             0,1 -> 4000000,4000001",
+        );
+        let sweep: SweepMap = lines.iter().collect();
+        // 1000001 shared vertical points, the two diagonals crossing the
+        // vertical below the shared part, and the diagonals crossing at
+        // (2000000, 2000000). The anti-diagonal only meets `y = x + 1`
+        // between lattice points.
+        assert_eq!(sweep.count_overlaps(), 1_000_001 + 2 + 1);
+    }
 }
@@ -0,0 +1,269 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::iter::FromIterator;
+
+use super::{LineDef, LineKind, Point};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum Family {
+    Horiz,
+    Vert,
+    Diag,
+    AntiDiag,
+}
+
+impl Family {
+    pub const ALL: [Self; 4] = [Self::Horiz, Self::Vert, Self::Diag, Self::AntiDiag];
+
+    pub const fn of(line: &LineDef) -> Option<Self> {
+        match line.kind() {
+            LineKind::Horizontal => Some(Self::Horiz),
+            LineKind::Vertical => Some(Self::Vert),
+            LineKind::Diagonal => Some(Self::Diag),
+            LineKind::AntiDiagonal => Some(Self::AntiDiag),
+            LineKind::Sloped => None,
+        }
+    }
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    // The line `a*x + b*y = key` that all lines of this family follow
+    const fn coefficients(self) -> (i64, i64) {
+        match self {
+            Self::Horiz => (0, 1),
+            Self::Vert => (1, 0),
+            Self::Diag => (1, -1),
+            Self::AntiDiag => (1, 1),
+        }
+    }
+
+    pub const fn key(self, p: Point) -> i64 {
+        let (a, b) = self.coefficients();
+        a * p.x as i64 + b * p.y as i64
+    }
+
+    pub const fn param(self, p: Point) -> i64 {
+        match self {
+            Self::Vert => p.y as i64,
+            _ => p.x as i64,
+        }
+    }
+
+    pub const fn point(self, key: i64, param: i64) -> Point {
+        let (x, y) = match self {
+            Self::Horiz => (param, key),
+            Self::Vert => (key, param),
+            Self::Diag => (param, param - key),
+            Self::AntiDiag => (param, key - param),
+        };
+        Point::new(x as i32, y as i32)
+    }
+
+    pub fn intersect(self, key: i64, other: Self, other_key: i64) -> Option<Point> {
+        let (a1, b1) = self.coefficients();
+        let (a2, b2) = other.coefficients();
+        let det = a1 * b2 - a2 * b1;
+        if det == 0 {
+            return None;
+        }
+        let x_num = key * b2 - other_key * b1;
+        let y_num = a1 * other_key - a2 * key;
+        if x_num % det != 0 || y_num % det != 0 {
+            return None;
+        }
+        Some(Point::new(
+            (x_num / det).try_into().ok()?,
+            (y_num / det).try_into().ok()?,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct Span {
+    key: i64,
+    lo: i64,
+    hi: i64,
+}
+
+impl Span {
+    pub const fn len(&self) -> i64 {
+        self.hi - self.lo + 1
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct FamilyCoverage {
+    covered: Vec<Span>,
+    overlapping: Vec<Span>,
+}
+
+impl FamilyCoverage {
+    pub fn new(spans: &[Span]) -> Self {
+        let mut events: Vec<(i64, i64, i32)> = spans
+            .iter()
+            .flat_map(|s| [(s.key, s.lo, 1), (s.key, s.hi + 1, -1)])
+            .collect();
+        events.sort_unstable();
+
+        let mut coverage = Self::default();
+        let mut depth = 0;
+        let mut covered_start = 0;
+        let mut overlap_start = 0;
+        for (key, pos, delta) in events {
+            let prev_depth = depth;
+            depth += delta;
+            if prev_depth == 0 && depth > 0 {
+                covered_start = pos;
+            } else if prev_depth > 0 && depth == 0 {
+                Self::push_span(&mut coverage.covered, key, covered_start, pos - 1);
+            }
+            if prev_depth < 2 && depth >= 2 {
+                overlap_start = pos;
+            } else if prev_depth >= 2 && depth < 2 {
+                Self::push_span(&mut coverage.overlapping, key, overlap_start, pos - 1);
+            }
+        }
+        coverage
+    }
+
+    // Spans that touch end-to-end come out of the sweep separately, so join them
+    fn push_span(spans: &mut Vec<Span>, key: i64, lo: i64, hi: i64) {
+        if lo > hi {
+            return;
+        }
+        match spans.last_mut() {
+            Some(last) if last.key == key && last.hi + 1 == lo => last.hi = hi,
+            _ => spans.push(Span { key, lo, hi }),
+        }
+    }
+
+    fn find(spans: &[Span], key: i64, param: i64) -> Option<&Span> {
+        let i = spans.partition_point(|s| (s.key, s.lo) <= (key, param));
+        spans[..i].last().filter(|s| s.key == key && s.hi >= param)
+    }
+
+    pub fn is_overlapping(&self, family: Family, p: Point) -> bool {
+        Self::find(&self.overlapping, family.key(p), family.param(p)).is_some()
+    }
+
+    pub fn is_covered(&self, family: Family, p: Point) -> bool {
+        Self::find(&self.covered, family.key(p), family.param(p)).is_some()
+    }
+
+    pub fn spans_with_keys(&self, lo: i64, hi: i64) -> &[Span] {
+        let start = self.covered.partition_point(|s| s.key < lo);
+        let end = self.covered.partition_point(|s| s.key <= hi);
+        &self.covered[start..end]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SweepMap {
+    spans: [Vec<Span>; 4],
+    sloped: Vec<LineDef>,
+}
+
+impl SweepMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_line(&mut self, line: &LineDef) {
+        let family = match Family::of(line) {
+            Some(family) => family,
+            None => {
+                self.sloped.push(line.clone());
+                return;
+            }
+        };
+        let (a, b) = (family.param(line.0), family.param(line.1));
+        self.spans[family.index()].push(Span {
+            key: family.key(line.0),
+            lo: a.min(b),
+            hi: a.max(b),
+        });
+    }
+
+    pub fn count_overlaps(&self) -> usize {
+        let coverage = Family::ALL.map(|f| FamilyCoverage::new(&self.spans[f.index()]));
+
+        // Points where lines of two different families cross
+        let mut crossings = HashSet::new();
+        for (i, &family) in Family::ALL.iter().enumerate() {
+            for &other in &Family::ALL[i + 1..] {
+                let other_coverage = &coverage[other.index()];
+                for span in &coverage[family.index()].covered {
+                    let start = family.point(span.key, span.lo);
+                    let end = family.point(span.key, span.hi);
+                    let (k1, k2) = (other.key(start), other.key(end));
+                    for other_span in other_coverage.spans_with_keys(k1.min(k2), k1.max(k2)) {
+                        let p = match family.intersect(span.key, other, other_span.key) {
+                            Some(p) => p,
+                            None => continue,
+                        };
+                        let param = family.param(p);
+                        let other_param = other.param(p);
+                        if (span.lo..=span.hi).contains(&param)
+                            && (other_span.lo..=other_span.hi).contains(&other_param)
+                        {
+                            crossings.insert(p);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut total: i64 = coverage
+            .iter()
+            .flat_map(|c| &c.overlapping)
+            .map(Span::len)
+            .sum();
+        // Each crossing was either missed above, or counted once per family
+        // in which it was already an overlap
+        for &p in &crossings {
+            let times_counted = Family::ALL
+                .iter()
+                .filter(|&&f| coverage[f.index()].is_overlapping(f, p))
+                .count() as i64;
+            total += 1 - times_counted;
+        }
+
+        // Sloped lines only pass through a lattice point every few steps, so
+        // their points are checked one at a time against everything else
+        let mut sloped_counts: HashMap<Point, i64> = HashMap::new();
+        for line in &self.sloped {
+            for p in line.points() {
+                *sloped_counts.entry(p).or_default() += 1;
+            }
+        }
+        for (p, sloped_count) in sloped_counts {
+            let already_counted = crossings.contains(&p)
+                || Family::ALL
+                    .iter()
+                    .any(|&f| coverage[f.index()].is_overlapping(f, p));
+            if already_counted {
+                continue;
+            }
+            let family_count = Family::ALL
+                .iter()
+                .filter(|&&f| coverage[f.index()].is_covered(f, p))
+                .count() as i64;
+            if family_count + sloped_count >= 2 {
+                total += 1;
+            }
+        }
+        total as usize
+    }
+}
+
+impl<T: Borrow<LineDef>> FromIterator<T> for SweepMap {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut map = SweepMap::new();
+        for line in iter {
+            map.add_line(line.borrow());
+        }
+        map
+    }
+}
@@ -1,10 +1,30 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::iter::FusedIterator;
 
-const REPEAT_SPAWN_DELAY: u32 = 7;
-const NEW_SPAWN_DELAY: u32 = 9;
+type Matrix = Vec<Vec<u128>>;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum PopulationErr {
+    InvalidTimer(u32),
+    Overflow,
+}
+
+impl Display for PopulationErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidTimer(timer) => write!(f, "invalid spawn timer: {}", timer),
+            Self::Overflow => write!(f, "population too large to represent"),
+        }
+    }
+}
+
+impl Error for PopulationErr {}
 
 pub fn fish_after_n_days(fish: impl IntoIterator<Item = impl Borrow<u32>>, num_days: u32) -> usize {
+    let model = SpawnModel::LANTERNFISH;
     let mut spawn_times: HashMap<u32, usize> = HashMap::new();
     for delay in fish.into_iter() {
         *spawn_times.entry(*delay.borrow()).or_default() += 1;
@@ -12,14 +32,309 @@ pub fn fish_after_n_days(fish: impl IntoIterator<Item = impl Borrow<u32>>, num_d
 
     for day in 0..num_days {
         if let Some(count) = spawn_times.remove(&day) {
-            *spawn_times.entry(day + REPEAT_SPAWN_DELAY).or_default() += count;
-            *spawn_times.entry(day + NEW_SPAWN_DELAY).or_default() += count;
+            *spawn_times.entry(day + model.repeat_delay).or_default() += count;
+            *spawn_times.entry(day + model.juvenile_delay).or_default() += count;
         }
     }
 
     spawn_times.drain().map(|(_, c)| c).sum()
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct SpawnModel {
+    repeat_delay: u32,
+    juvenile_delay: u32,
+    litter_size: u32,
+    lifespan: Option<u32>,
+}
+
+impl SpawnModel {
+    pub const LANTERNFISH: Self = Self::new(7, 9);
+
+    pub const fn new(repeat_delay: u32, juvenile_delay: u32) -> Self {
+        assert!(
+            repeat_delay > 0 && juvenile_delay > 0,
+            "delays must be positive"
+        );
+        SpawnModel {
+            repeat_delay,
+            juvenile_delay,
+            litter_size: 1,
+            lifespan: None,
+        }
+    }
+
+    pub const fn with_litter_size(self, litter_size: u32) -> Self {
+        SpawnModel {
+            litter_size,
+            ..self
+        }
+    }
+
+    pub const fn with_lifespan(self, lifespan: u32) -> Self {
+        assert!(lifespan > 0, "lifespan must be positive");
+        SpawnModel {
+            lifespan: Some(lifespan),
+            ..self
+        }
+    }
+
+    // Fish are tracked by age. Without a lifespan, every adult age collapses
+    // onto its position within the repeating spawn cycle.
+    fn num_ages(&self) -> usize {
+        match self.lifespan {
+            Some(lifespan) => lifespan as usize,
+            None => (self.juvenile_delay + self.repeat_delay - 1) as usize,
+        }
+    }
+
+    const fn first_spawn_age(&self) -> u32 {
+        self.juvenile_delay - 1
+    }
+
+    fn spawns_at(&self, age: u32) -> bool {
+        age >= self.first_spawn_age()
+            && (age - self.first_spawn_age()).is_multiple_of(self.repeat_delay)
+    }
+
+    fn next_age(&self, age: u32) -> Option<u32> {
+        let next = age + 1;
+        if (next as usize) < self.num_ages() {
+            Some(next)
+        } else if self.lifespan.is_none() {
+            Some(self.first_spawn_age())
+        } else {
+            None
+        }
+    }
+
+    // Maps the number of fish of each age to the numbers a day later
+    fn transition(&self) -> Matrix {
+        let n = self.num_ages();
+        let mut m = vec![vec![0; n]; n];
+        for age in 0..n as u32 {
+            if let Some(next) = self.next_age(age) {
+                m[next as usize][age as usize] += 1;
+            }
+            if self.spawns_at(age) {
+                m[0][age as usize] += self.litter_size as u128;
+            }
+        }
+        m
+    }
+
+    // Fish from the input have unknown ages, so once they can die they're
+    // tracked by timer instead, living `lifespan` days from the start. Without
+    // a lifespan a fish's timer is all that matters about its age.
+    fn initial_population(
+        &self,
+        fish: impl IntoIterator<Item = impl Borrow<u32>>,
+    ) -> Result<Population, PopulationErr> {
+        let mut population = Population {
+            ages: vec![0; self.num_ages()],
+            elders: match self.lifespan {
+                Some(_) => vec![0; self.juvenile_delay.max(self.repeat_delay) as usize],
+                None => vec![],
+            },
+            day: 0,
+        };
+        for timer in fish {
+            let timer = *timer.borrow();
+            let slot = match self.lifespan {
+                _ if timer > self.first_spawn_age() => None,
+                Some(_) => population.elders.get_mut(timer as usize),
+                None => population
+                    .ages
+                    .get_mut((self.first_spawn_age() - timer) as usize),
+            };
+            *slot.ok_or(PopulationErr::InvalidTimer(timer))? += 1;
+        }
+        Ok(population)
+    }
+
+    fn step(
+        &self,
+        transition: &Matrix,
+        mut population: Population,
+        modulus: Option<u64>,
+    ) -> Result<Population, PopulationErr> {
+        population.ages = mat_vec(transition, &population.ages, modulus)?;
+        population.day += 1;
+        if !population.elders.is_empty() {
+            let spawning = population.elders.remove(0);
+            population.elders.push(0);
+            let reset = self.repeat_delay as usize - 1;
+            population.elders[reset] = mul_add(population.elders[reset], spawning, 1, modulus)?;
+            population.ages[0] = mul_add(
+                population.ages[0],
+                spawning,
+                self.litter_size as u128,
+                modulus,
+            )?;
+            if self
+                .lifespan
+                .is_some_and(|lifespan| population.day >= lifespan as u64)
+            {
+                population.elders.clear();
+            }
+        }
+        Ok(population)
+    }
+
+    fn population(
+        &self,
+        fish: impl IntoIterator<Item = impl Borrow<u32>>,
+        num_days: u64,
+        modulus: Option<u64>,
+    ) -> Result<u128, PopulationErr> {
+        let transition = self.transition();
+        let mut population = self.initial_population(fish)?;
+        // Step through the days the input fish are alive, after which only
+        // fish of known ages are left
+        let mut num_days = num_days;
+        while !population.elders.is_empty() && num_days > 0 {
+            population = self.step(&transition, population, modulus)?;
+            num_days -= 1;
+        }
+        let m = mat_pow(&transition, num_days, modulus)?;
+        population.ages = mat_vec(&m, &population.ages, modulus)?;
+        population.total(modulus)
+    }
+
+    pub fn population_after(
+        &self,
+        fish: impl IntoIterator<Item = impl Borrow<u32>>,
+        num_days: u64,
+    ) -> Result<u128, PopulationErr> {
+        self.population(fish, num_days, None)
+    }
+
+    pub fn population_after_mod(
+        &self,
+        fish: impl IntoIterator<Item = impl Borrow<u32>>,
+        num_days: u64,
+        modulus: u64,
+    ) -> Result<u64, PopulationErr> {
+        assert!(modulus > 0, "modulus must be positive");
+        self.population(fish, num_days, Some(modulus))
+            .map(|n| n as u64)
+    }
+
+    pub fn time_series(
+        &self,
+        fish: impl IntoIterator<Item = impl Borrow<u32>>,
+    ) -> Result<PopulationIter, PopulationErr> {
+        Ok(PopulationIter {
+            model: *self,
+            transition: self.transition(),
+            population: Some(Ok(self.initial_population(fish)?)),
+        })
+    }
+}
+
+impl Default for SpawnModel {
+    fn default() -> Self {
+        Self::LANTERNFISH
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct Population {
+    ages: Vec<u128>,
+    // Input fish still alive, by timer
+    elders: Vec<u128>,
+    day: u64,
+}
+
+impl Population {
+    fn total(&self, modulus: Option<u64>) -> Result<u128, PopulationErr> {
+        self.ages
+            .iter()
+            .chain(&self.elders)
+            .try_fold(0, |total, &count| mul_add(total, count, 1, modulus))
+    }
+}
+
+pub struct PopulationIter {
+    model: SpawnModel,
+    transition: Matrix,
+    // An error stepping to the next day is held until that day is reached
+    population: Option<Result<Population, PopulationErr>>,
+}
+
+impl Iterator for PopulationIter {
+    type Item = Result<u128, PopulationErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.population.take()?.and_then(|population| {
+            let total = population.total(None)?;
+            self.population = Some(self.model.step(&self.transition, population, None));
+            Ok(total)
+        });
+        Some(result)
+    }
+}
+
+impl FusedIterator for PopulationIter {}
+
+fn identity(n: usize) -> Matrix {
+    let mut m = vec![vec![0; n]; n];
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = 1;
+    }
+    m
+}
+
+fn mul_add(acc: u128, a: u128, b: u128, modulus: Option<u64>) -> Result<u128, PopulationErr> {
+    match modulus {
+        // Both operands are already reduced below 2^64, so nothing can overflow
+        Some(m) => Ok((acc + a * b % m as u128) % m as u128),
+        None => a
+            .checked_mul(b)
+            .and_then(|p| p.checked_add(acc))
+            .ok_or(PopulationErr::Overflow),
+    }
+}
+
+fn mat_vec(m: &Matrix, v: &[u128], modulus: Option<u64>) -> Result<Vec<u128>, PopulationErr> {
+    m.iter()
+        .map(|row| {
+            row.iter()
+                .zip(v)
+                .try_fold(0, |acc, (a, b)| mul_add(acc, *a, *b, modulus))
+        })
+        .collect()
+}
+
+fn mat_mul(a: &Matrix, b: &Matrix, modulus: Option<u64>) -> Result<Matrix, PopulationErr> {
+    let n = a.len();
+    let mut result = vec![vec![0; n]; n];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            for k in 0..n {
+                *cell = mul_add(*cell, a[i][k], b[k][j], modulus)?;
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn mat_pow(base: &Matrix, mut exp: u64, modulus: Option<u64>) -> Result<Matrix, PopulationErr> {
+    let mut result = identity(base.len());
+    let mut base = base.clone();
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mat_mul(&result, &base, modulus)?;
+        }
+        exp >>= 1;
+        // Squaring past the highest bit could overflow needlessly
+        if exp > 0 {
+            base = mat_mul(&base, &base, modulus)?;
+        }
+    }
+    Ok(result)
+}
+
 pub fn main() {
     let input: Vec<u32> = include_str!("input.txt")
         .trim()
@@ -29,4 +344,146 @@ pub fn main() {
 
     println!("P1: after 80 days: {}", fish_after_n_days(&input, 80));
     println!("P2: after 256 days: {}", fish_after_n_days(&input, 256));
+
+    let model = SpawnModel::LANTERNFISH;
+    for num_days in [256, 800, 1000] {
+        match model.population_after(&input, num_days) {
+            Ok(count) => println!("After {} days: {}", num_days, count),
+            Err(err) => println!("After {} days: {}", num_days, err),
+        }
+    }
+    const PRIME: u64 = 1_000_000_007;
+    println!(
+        "After 10^12 days: {} (mod {})",
+        model
+            .population_after_mod(&input, 1_000_000_000_000, PRIME)
+            .unwrap(),
+        PRIME
+    );
+
+    let variants = [
+        ("Twins", model.with_litter_size(2)),
+        ("Mortal", model.with_lifespan(50)),
+        ("Fast", SpawnModel::new(5, 6)),
+    ];
+    for (name, variant) in variants {
+        let series: Vec<_> = variant
+            .time_series(&input)
+            .unwrap()
+            .step_by(10)
+            .take(9)
+            .map(Result::unwrap)
+            .collect();
+        println!("{} every 10 days: {:?}", name, series);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_FISH: [u32; 5] = [3, 4, 3, 1, 2];
+
+    #[test]
+    fn matrix_matches_simulation() {
+        let model = SpawnModel::LANTERNFISH;
+        for num_days in [0, 1, 18, 80, 256] {
+            assert_eq!(
+                model.population_after(TEST_FISH, num_days as u64),
+                Ok(fish_after_n_days(TEST_FISH, num_days) as u128)
+            );
+        }
+        assert_eq!(model.population_after(TEST_FISH, 256), Ok(26984457539));
+    }
+
+    #[test]
+    fn time_series_matches_matrix() {
+        let model = SpawnModel::new(4, 6).with_litter_size(3).with_lifespan(20);
+        let series: Vec<u128> = model
+            .time_series(TEST_FISH)
+            .unwrap()
+            .take(100)
+            .map(Result::unwrap)
+            .collect();
+        for (day, count) in series.into_iter().enumerate() {
+            assert_eq!(model.population_after(TEST_FISH, day as u64), Ok(count));
+        }
+    }
+
+    #[test]
+    fn lifespan_limits_population() {
+        // Nothing has died yet
+        let mortal = SpawnModel::LANTERNFISH.with_lifespan(100);
+        for num_days in [0, 1, 50, 99] {
+            assert_eq!(
+                mortal.population_after(TEST_FISH, num_days),
+                SpawnModel::LANTERNFISH.population_after(TEST_FISH, num_days)
+            );
+        }
+
+        // Each newborn spawns exactly once before dying
+        let model = SpawnModel::LANTERNFISH.with_lifespan(9);
+        for num_days in [0, 1, 9, 100] {
+            assert_eq!(model.population_after([8], num_days), Ok(1));
+        }
+
+        // Newborns die before they are old enough to spawn, but a fish that's
+        // about to spawn still does
+        let model = SpawnModel::LANTERNFISH.with_lifespan(8);
+        for (num_days, count) in [(0, 1), (1, 2), (8, 2), (9, 1), (16, 0)] {
+            assert_eq!(model.population_after([0], num_days), Ok(count));
+        }
+        let series: Vec<u128> = model
+            .time_series([0])
+            .unwrap()
+            .take(17)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(series[8..], [2, 1, 1, 1, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn modular_population() {
+        let model = SpawnModel::LANTERNFISH;
+        let exact = model.population_after(TEST_FISH, 500).unwrap();
+        assert_eq!(
+            model.population_after_mod(TEST_FISH, 500, 1_000_000_007),
+            Ok((exact % 1_000_000_007) as u64)
+        );
+    }
+
+    #[test]
+    fn population_errors() {
+        let model = SpawnModel::LANTERNFISH;
+        assert_eq!(
+            model.population_after(TEST_FISH, 10_000),
+            Err(PopulationErr::Overflow)
+        );
+        assert_eq!(
+            model.population_after([3, 9], 1),
+            Err(PopulationErr::InvalidTimer(9))
+        );
+        let mut series = model.time_series(TEST_FISH).unwrap();
+        assert_eq!(
+            series.find(Result::is_err),
+            Some(Err(PopulationErr::Overflow))
+        );
+        assert_eq!(series.next(), None);
+
+        // Each day multiplies the population by 2^32
+        let model = SpawnModel::new(1, 1).with_litter_size(u32::MAX);
+        assert_eq!(model.population_after([0], 3), Ok(1 << 96));
+        assert_eq!(model.population_after([0], 4), Err(PopulationErr::Overflow));
+        let series: Vec<_> = model.time_series([0]).unwrap().collect();
+        assert_eq!(
+            series,
+            [
+                Ok(1),
+                Ok(1 << 32),
+                Ok(1 << 64),
+                Ok(1 << 96),
+                Err(PopulationErr::Overflow)
+            ]
+        );
+    }
 }
@@ -1,17 +1,19 @@
 use std::borrow::Borrow;
+use std::ops::RangeInclusive;
 
 mod p1 {
-    pub fn dist(a: u32, b: u32) -> u32 {
+    pub fn dist(a: u32, b: u32) -> u64 {
         if a > b {
-            a - b
+            (a - b) as u64
         } else {
-            b - a
+            (b - a) as u64
         }
     }
 }
 
 mod p2 {
-    pub fn dist(a: u32, b: u32) -> u32 {
+    // Distances are under 2^32, so this stays under 2^64
+    pub fn dist(a: u32, b: u32) -> u64 {
         let d = super::p1::dist(a, b);
         (d * d + d) / 2
     }
@@ -19,33 +21,225 @@ mod p2 {
 
 pub fn fuel_to(
     crabs: impl IntoIterator<Item = impl Borrow<u32>>,
-    dist: impl Fn(u32, u32) -> u32,
+    dist: impl Fn(u32, u32) -> u64,
     point: u32,
-) -> u32 {
-    crabs.into_iter().map(|n| dist(*n.borrow(), point)).sum()
+) -> Option<u64> {
+    crabs
+        .into_iter()
+        .try_fold(0u64, |total, n| total.checked_add(dist(*n.borrow(), point)))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alignment {
+    pub positions: RangeInclusive<u32>,
+    pub fuel: u64,
+}
+
+// Finds the first position in `lo..=hi` at which the total fuel stops
+// decreasing (or starts increasing, if `strict`). For a convex cost this is
+// a monotonic predicate, so it can be binary searched.
+fn find_turn(fuel: impl Fn(u32) -> u64, mut lo: u32, mut hi: u32, strict: bool) -> u32 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let (here, next) = (fuel(mid), fuel(mid + 1));
+        if next > here || (!strict && next == here) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
+pub fn align(
+    crabs: impl IntoIterator<Item = impl Borrow<u32>>,
+    cost: impl Fn(u32, u32) -> u64,
+) -> Option<Alignment> {
+    let crabs: Vec<u32> = crabs.into_iter().map(|n| *n.borrow()).collect();
+    let min = *crabs.iter().min()?;
+    let max = *crabs.iter().max()?;
+    // For a convex cost the fuel in between never exceeds the fuel at the
+    // ends, so if both of those fit, every position does
+    let checked_fuel = |pos| fuel_to(&crabs, &cost, pos);
+    checked_fuel(min)?;
+    checked_fuel(max)?;
+    let fuel = |pos| checked_fuel(pos).unwrap();
+
+    let first = find_turn(fuel, min, max, false);
+    let last = find_turn(fuel, first, max, true);
+    Some(Alignment {
+        positions: first..=last,
+        fuel: fuel(first),
+    })
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum FuelCost {
+    Linear,
+    Triangular,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuelProfile {
+    sorted: Vec<u32>,
+    prefix_sums: Vec<u128>,
+    sum_of_squares: u128,
+}
+
+impl FuelProfile {
+    pub fn new(crabs: impl IntoIterator<Item = impl Borrow<u32>>) -> Self {
+        let mut sorted: Vec<u32> = crabs.into_iter().map(|n| *n.borrow()).collect();
+        sorted.sort_unstable();
+        let mut prefix_sums = Vec::with_capacity(sorted.len() + 1);
+        prefix_sums.push(0);
+        let mut sum = 0;
+        for &crab in &sorted {
+            sum += crab as u128;
+            prefix_sums.push(sum);
+        }
+        let sum_of_squares = sorted.iter().map(|&c| c as u128 * c as u128).sum();
+        FuelProfile {
+            sorted,
+            prefix_sums,
+            sum_of_squares,
+        }
+    }
+
+    pub fn range(&self) -> Option<RangeInclusive<u32>> {
+        Some(*self.sorted.first()?..=*self.sorted.last()?)
+    }
+
+    fn linear_wide(&self, pos: u32) -> u128 {
+        let n = self.sorted.len() as u128;
+        let total = self.prefix_sums[self.sorted.len()];
+        let below = self.sorted.partition_point(|&c| c <= pos);
+        let below_sum = self.prefix_sums[below];
+        let (pos, below) = (pos as u128, below as u128);
+        (pos * below - below_sum) + ((total - below_sum) - pos * (n - below))
+    }
+
+    pub fn linear(&self, pos: u32) -> Option<u64> {
+        self.linear_wide(pos).try_into().ok()
+    }
+
+    pub fn triangular(&self, pos: u32) -> Option<u64> {
+        let n = self.sorted.len() as u128;
+        let total = self.prefix_sums[self.sorted.len()];
+        let pos_wide = pos as u128;
+        let squares = self.sum_of_squares + n * pos_wide * pos_wide - 2 * pos_wide * total;
+        ((squares + self.linear_wide(pos)) / 2).try_into().ok()
+    }
+
+    pub fn fuel(&self, cost: FuelCost, pos: u32) -> Option<u64> {
+        match cost {
+            FuelCost::Linear => self.linear(pos),
+            FuelCost::Triangular => self.triangular(pos),
+        }
+    }
+
+    pub fn curve(&self, cost: FuelCost) -> impl Iterator<Item = (u32, Option<u64>)> + '_ {
+        self.range()
+            .into_iter()
+            .flatten()
+            .map(move |pos| (pos, self.fuel(cost, pos)))
+    }
 }
 
 pub fn main() {
-    let mut crabs: Vec<u32> = include_str!("input.txt")
+    let crabs: Vec<u32> = include_str!("input.txt")
         .trim()
         .split(',')
         .map(|n| n.parse().unwrap())
         .collect();
 
-    crabs.sort();
-    let target_pos = crabs[crabs.len() / 2];
-    let fuel_usage: u32 = fuel_to(&crabs, p1::dist, target_pos);
-    println!("P1: {} fuel to {}", fuel_usage, target_pos);
-
-    let sum: u32 = crabs.iter().copied().sum();
-    let mean_floor = sum / crabs.len() as u32;
-    let mean_ceil = mean_floor + 1;
-    let mean_floor_fuel = fuel_to(&crabs, p2::dist, mean_floor);
-    let mean_ceil_fuel = fuel_to(&crabs, p2::dist, mean_ceil);
-    let (target_pos, fuel_usage) = if mean_ceil_fuel < mean_floor_fuel {
-        (mean_ceil, mean_ceil_fuel)
-    } else {
-        (mean_floor, mean_floor_fuel)
-    };
-    println!("P2: {} fuel to {}", fuel_usage, target_pos);
+    let alignment = align(&crabs, p1::dist).unwrap();
+    println!("P1: {} fuel to {:?}", alignment.fuel, alignment.positions);
+
+    let alignment = align(&crabs, p2::dist).unwrap();
+    println!("P2: {} fuel to {:?}", alignment.fuel, alignment.positions);
+
+    let profile = FuelProfile::new(&crabs);
+    for (part, cost) in [("P1", FuelCost::Linear), ("P2", FuelCost::Triangular)] {
+        let (pos, fuel) = profile
+            .curve(cost)
+            .filter_map(|(pos, fuel)| Some((pos, fuel?)))
+            .min_by_key(|&(pos, fuel)| (fuel, pos))
+            .unwrap();
+        println!("{} (profile): {} fuel to {}", part, fuel, pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CRABS: [u32; 10] = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+
+    #[test]
+    fn aligns_example() {
+        assert_eq!(
+            align(TEST_CRABS, p1::dist),
+            Some(Alignment {
+                positions: 2..=2,
+                fuel: 37,
+            })
+        );
+        assert_eq!(
+            align(TEST_CRABS, p2::dist),
+            Some(Alignment {
+                positions: 5..=5,
+                fuel: 168,
+            })
+        );
+    }
+
+    #[test]
+    fn profile_matches_fuel_to() {
+        let profile = FuelProfile::new(TEST_CRABS);
+        assert_eq!(profile.range(), Some(0..=16));
+        for pos in 0..=20 {
+            assert_eq!(profile.linear(pos), fuel_to(TEST_CRABS, p1::dist, pos));
+            assert_eq!(profile.triangular(pos), fuel_to(TEST_CRABS, p2::dist, pos));
+        }
+        assert_eq!(profile.curve(FuelCost::Linear).count(), 17);
+    }
+
+    #[test]
+    fn profile_does_not_overflow() {
+        let profile = FuelProfile::new([0, u32::MAX, u32::MAX]);
+        assert_eq!(profile.linear(0), Some(2 * u32::MAX as u64));
+        let d = u32::MAX as u64;
+        assert_eq!(profile.triangular(0), Some(d * (d + 1)));
+        assert_eq!(FuelProfile::new([0; 5]).triangular(u32::MAX), None);
+    }
+
+    #[test]
+    fn reports_ties() {
+        assert_eq!(
+            align([1, 2, 7, 10], p1::dist),
+            Some(Alignment {
+                positions: 2..=7,
+                fuel: 14,
+            })
+        );
+        assert_eq!(align([] as [u32; 0], p1::dist), None);
+    }
+
+    #[test]
+    fn aligns_wide_spread() {
+        // Each crab is 100_000 from the middle
+        assert_eq!(
+            align([0, 200_000], p2::dist),
+            Some(Alignment {
+                positions: 100_000..=100_000,
+                fuel: 100_000 * 100_001,
+            })
+        );
+        assert_eq!(p2::dist(0, u32::MAX), (1 << 63) - (1 << 31));
+
+        // Each crab's cost fits, but the total doesn't
+        let crabs: Vec<u32> = [0, u32::MAX].iter().flat_map(|&c| [c; 10]).collect();
+        assert_eq!(fuel_to(&crabs, p2::dist, 0), None);
+        assert_eq!(align(&crabs, p2::dist), None);
+    }
 }
@@ -1,13 +1,21 @@
 #![feature(const_char_convert)]
 #![feature(const_for)]
 use std::borrow::Borrow;
-use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::fmt::{Debug, Display, Write};
 use std::iter::FromIterator;
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
 use std::str::FromStr;
 
+mod err;
+use err::*;
+mod display;
+use display::{diagnose, render, FaultModel, RenderStyle};
+mod glyphs;
+use glyphs::{parse_radix, GlyphSet};
+mod solver;
+use solver::{decode_all, solve_wiring, solve_wirings, solve_wirings_with, Wiring, WiringErr};
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Seg {
@@ -61,29 +69,29 @@ impl Seg {
 }
 
 impl TryFrom<u8> for Seg {
-    type Error = ();
+    type Error = ParseSegErr;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        Self::from_u8(value).ok_or(())
+        Self::from_u8(value).ok_or(ParseSegErr::InvalidIndex(value))
     }
 }
 
 impl TryFrom<char> for Seg {
-    type Error = ();
+    type Error = ParseSegErr;
 
     fn try_from(value: char) -> Result<Self, Self::Error> {
-        Self::from_char(value).ok_or(())
+        Self::from_char(value).ok_or(ParseSegErr::InvalidChar(value))
     }
 }
 
 impl FromStr for Seg {
-    type Err = ();
+    type Err = ParseSegErr;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let [c] = s.as_bytes() {
-            (*c as char).try_into()
-        } else {
-            Err(())
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => c.try_into(),
+            _ => Err(ParseSegErr::WrongLength(s.chars().count())),
         }
     }
 }
@@ -116,6 +124,10 @@ impl Segments {
         Segments(bits & ((1 << 7) - 1))
     }
 
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
     pub const fn len(&self) -> u8 {
         self.0.count_ones() as u8
     }
@@ -129,24 +141,7 @@ impl Segments {
     }
 
     pub fn print(&self) {
-        let get_fill = |seg: u8| {
-            if self.0 & (1 << seg) != 0 {
-                '#'
-            } else {
-                ' '
-            }
-        };
-        let b = get_fill(1);
-        let c = get_fill(2);
-        let e = get_fill(4);
-        let f = get_fill(5);
-        println!(" {0}{0}{0} ", get_fill(0));
-        println!("{}   {}", b, c);
-        println!("{}   {}", b, c);
-        println!(" {0}{0}{0} ", get_fill(3));
-        println!("{}   {}", e, f);
-        println!("{}   {}", e, f);
-        println!(" {0}{0}{0} ", get_fill(6));
+        print!("{}", render([*self], RenderStyle::Ascii));
     }
 }
 
@@ -184,6 +179,14 @@ impl BitAndAssign for Segments {
     }
 }
 
+impl Not for Segments {
+    type Output = Segments;
+
+    fn not(self) -> Self::Output {
+        Segments::from_bits_masked(!self.0)
+    }
+}
+
 impl From<Seg> for Segments {
     fn from(value: Seg) -> Self {
         Segments(1 << value as u8)
@@ -191,12 +194,15 @@ impl From<Seg> for Segments {
 }
 
 impl FromStr for Segments {
-    type Err = ();
+    type Err = ParseSegmentsErr;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut res = Segments::none();
         for c in s.chars() {
-            let seg: Seg = c.try_into()?;
+            let seg = Seg::from_char(c).ok_or(ParseSegmentsErr::InvalidChar(c))?;
+            if res.contains(seg.into()) {
+                return Err(ParseSegmentsErr::Duplicate(seg));
+            }
             res |= seg.into();
         }
         Ok(res)
@@ -334,12 +340,10 @@ impl Digit {
     }
 
     pub fn parse(digits: impl IntoIterator<Item = impl Borrow<Digit>>) -> u32 {
-        let mut value = 0;
-        for d in digits.into_iter() {
-            value *= 10;
-            value += *d.borrow() as u32;
-        }
+        let value = parse_radix(digits.into_iter().map(|d| *d.borrow() as u32), 10);
         value
+            .and_then(|v| v.try_into().ok())
+            .expect("number too large")
     }
 }
 
@@ -379,42 +383,58 @@ impl<T: AsSegments> AsSegments for &T {
     }
 }
 
-type SegId = [u8; 6];
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct DisplayEntry {
+    pub patterns: [Segments; Self::NUM_PATTERNS],
+    pub outputs: [Segments; Self::NUM_OUTPUTS],
+}
 
-pub fn calc_segment_ids(all_states: impl IntoIterator<Item = impl AsSegments>) -> [SegId; 7] {
-    let mut ids = [[0u8; 6]; 7];
-    for state in all_states {
-        let segments = state.as_segments();
-        for s in segments {
-            ids[s as usize][segments.len() as usize - 2] += 1;
-        }
+impl DisplayEntry {
+    pub const NUM_PATTERNS: usize = 10;
+    pub const NUM_OUTPUTS: usize = 4;
+}
+
+impl FromStr for DisplayEntry {
+    type Err = DisplayEntryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        type Err = DisplayEntryParseError;
+
+        let (patterns, outputs) = s.split_once('|').ok_or(Err::MissingDelimiter)?;
+        let patterns: Vec<Segments> = patterns
+            .split_whitespace()
+            .enumerate()
+            .map(|(i, p)| p.parse().map_err(|err| Err::InvalidPattern(i, err)))
+            .collect::<Result<_, _>>()?;
+        let outputs: Vec<Segments> = outputs
+            .split_whitespace()
+            .enumerate()
+            .map(|(i, p)| p.parse().map_err(|err| Err::InvalidOutput(i, err)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(DisplayEntry {
+            patterns: patterns
+                .try_into()
+                .map_err(|p: Vec<_>| Err::WrongPatternCount(p.len()))?,
+            outputs: outputs
+                .try_into()
+                .map_err(|o: Vec<_>| Err::WrongOutputCount(o.len()))?,
+        })
     }
-    ids
 }
 
 pub fn main() {
-    let input: Vec<_> = include_str!("input.txt")
+    let input: Vec<DisplayEntry> = include_str!("input.txt")
         .lines()
-        .map(|s| {
-            let (all_states, output) = s.split_once("|").unwrap();
-            let all_states: Vec<Segments> = all_states
-                .split_whitespace()
-                .map(|s| s.parse().unwrap())
-                .collect();
-            let output: Vec<Segments> = output
-                .split_whitespace()
-                .map(|s| s.parse().unwrap())
-                .collect();
-            (all_states, output)
-        })
+        .map(|s| s.parse().unwrap())
         .collect();
 
     let unique_len_digits = [Digit::D1, Digit::D4, Digit::D7, Digit::D8];
     let unique_digit_lens = unique_len_digits.map(|d| d.segments().len());
 
     let mut unique_lens_in_output = 0;
-    for (_, output) in &input {
-        for seg in output {
+    for entry in &input {
+        for seg in &entry.outputs {
             if unique_digit_lens.contains(&seg.len()) {
                 unique_lens_in_output += 1;
             }
@@ -423,22 +443,186 @@ pub fn main() {
 
     println!("P1: {} unique lengths in output", unique_lens_in_output);
 
-    let normal_seg_ids: HashMap<SegId, Seg> = calc_segment_ids(Digit::values())
-        .into_iter()
-        .enumerate()
-        .map(|(i, id)| (id, Seg::from_u8(i as u8).unwrap()))
+    let mut output_sum = 0;
+    for entry in &input {
+        let wiring = solve_wiring(entry.patterns.iter().chain(&entry.outputs)).unwrap();
+        let output = Digit::parse(entry.outputs.iter().map(|s| wiring.decode(s).unwrap()));
+        output_sum += output;
+    }
+    println!("P2: sum of outputs is {}", output_sum);
+
+    let mut decodable = 0;
+    let mut ambiguous = 0;
+    for entry in &input {
+        let outputs = &entry.outputs;
+        match decode_all(&GlyphSet::decimal(), &solve_wirings(outputs), outputs) {
+            Ok(_) => decodable += 1,
+            Err(WiringErr::Ambiguous(_)) => ambiguous += 1,
+            Err(WiringErr::Impossible) => {}
+        }
+    }
+    println!(
+        "From outputs alone: {} decodable, {} ambiguous",
+        decodable, ambiguous
+    );
+
+    // Scramble a hexadecimal display using the first entry's wiring, then
+    // recover it
+    let hex = GlyphSet::hexadecimal();
+    let scramble = solve_wiring(input[0].patterns).unwrap().inverse();
+    let message = [0xD, 0xE, 0xC, 0xA, 0xF];
+    let observed: Vec<Segments> = hex
+        .glyphs()
+        .iter()
+        .copied()
+        .chain(message.map(|v| hex.segments_of(v).unwrap()))
+        .map(|g| scramble.apply(g))
         .collect();
+    let wiring = solve_wirings_with(&hex, &observed)[0];
+    let decoded = hex.parse(
+        observed[hex.glyphs().len()..]
+            .iter()
+            .map(|&s| wiring.apply(s)),
+    );
+    println!("Hex: decoded {:X}", decoded.unwrap());
+
+    let faulty = FaultModel::healthy()
+        .with_stuck_on(Seg::G)
+        .with_stuck_off(Seg::D)
+        .with_swapped(Seg::B, Seg::E);
+    let readings: Vec<(Segments, Segments)> = Digit::values()
+        .iter()
+        .map(|d| (d.segments(), faulty.apply(d)))
+        .collect();
+    println!("Faulty display:");
+    print!(
+        "{}",
+        render(readings.iter().map(|(_, r)| r), RenderStyle::Unicode)
+    );
+    for model in diagnose(&readings) {
+        println!(
+            "Diagnosis: stuck on {:?}, stuck off {:?}, wiring {:?}",
+            model.stuck_on(),
+            model.stuck_off(),
+            model.wiring()
+        );
+    }
+}
 
-    let mut output_sum = 0;
-    for (all_states, output) in &input {
-        let shifted_segs = calc_segment_ids(all_states)
-            .map(|id| *normal_seg_ids.get(&id).expect("impossible layout"));
-        let output = Digit::parse(
-            output
-                .into_iter()
-                .map(|s| Digit::from_segments(s.map(|s| shifted_segs[s as usize])).unwrap()),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segs(patterns: &str) -> Vec<Segments> {
+        patterns
+            .split_whitespace()
+            .map(|s| s.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn solves_full_observation() {
+        let patterns = segs("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab");
+        let wiring = solve_wiring(&patterns).unwrap();
+        let output = segs("cdfeb fcadb cdfeb cdbaf");
+        assert_eq!(
+            Digit::parse(output.iter().map(|s| wiring.decode(s).unwrap())),
+            5353
+        );
+    }
+
+    #[test]
+    fn renders_side_by_side() {
+        let rendered = render([Digit::D1, Digit::D7], RenderStyle::Ascii);
+        let rows: Vec<&str> = rendered.lines().collect();
+        assert_eq!(rows.len(), 7);
+        assert_eq!(rows[0], "       ### ");
+        assert_eq!(rows[1], "    #     #");
+        assert_eq!(rows[3], "           ");
+    }
+
+    #[test]
+    fn diagnoses_faults() {
+        let faulty = FaultModel::healthy()
+            .with_stuck_on(Seg::A)
+            .with_swapped(Seg::C, Seg::F);
+        let readings: Vec<_> = Digit::values()
+            .iter()
+            .map(|d| (d.segments(), faulty.apply(d)))
+            .collect();
+        assert_eq!(diagnose(&readings), vec![faulty]);
+
+        let healthy: Vec<_> = readings.iter().map(|&(d, _)| (d, d)).collect();
+        assert_eq!(diagnose(&healthy), vec![FaultModel::healthy()]);
+
+        // A segment that is never meant to light can't be told apart from a
+        // dead one, so the healthy explanation wins
+        let readings = [(Digit::D1.segments(), Digit::D1.segments())];
+        assert_eq!(diagnose(&readings), vec![FaultModel::healthy()]);
+    }
+
+    #[test]
+    fn parses_entries_strictly() {
+        type Err = DisplayEntryParseError;
+
+        let line = include_str!("test.txt").lines().next().unwrap();
+        let entry: DisplayEntry = line.parse().unwrap();
+        assert_eq!(entry.patterns[0], "be".parse().unwrap());
+        assert_eq!(entry.outputs[3], "gcbe".parse().unwrap());
+
+        assert_eq!("ab cd".parse::<DisplayEntry>(), Err(Err::MissingDelimiter));
+        assert_eq!(
+            "ab cx | ab".parse::<DisplayEntry>(),
+            Err(Err::InvalidPattern(1, ParseSegmentsErr::InvalidChar('x')))
+        );
+        assert_eq!(
+            "ab | ab bcb".parse::<DisplayEntry>(),
+            Err(Err::InvalidOutput(1, ParseSegmentsErr::Duplicate(Seg::B)))
+        );
+        assert_eq!(
+            "ab | ab".parse::<DisplayEntry>(),
+            Err(Err::WrongPatternCount(1))
+        );
+        let (patterns, _) = line.split_once('|').unwrap();
+        assert_eq!(
+            format!("{}| ab", patterns).parse::<DisplayEntry>(),
+            Err(Err::WrongOutputCount(1))
+        );
+
+        assert_eq!("ab".parse::<Seg>(), Err(ParseSegErr::WrongLength(2)));
+        assert_eq!("h".parse::<Seg>(), Err(ParseSegErr::InvalidChar('h')));
+        assert_eq!(Seg::try_from(7u8), Err(ParseSegErr::InvalidIndex(7)));
+    }
+
+    #[test]
+    fn parses_hexadecimal() {
+        let hex = GlyphSet::hexadecimal();
+        assert_eq!(hex.radix(), 16);
+        let glyphs = [1, 0xA, 0xC, 0xE].map(|v| hex.segments_of(v).unwrap());
+        assert_eq!(hex.parse(glyphs), Some(0x1ACE));
+        assert_eq!(GlyphSet::decimal().parse(glyphs), None);
+        assert_eq!(parse_radix([1, 2], 2), None);
+    }
+
+    #[test]
+    fn reports_ambiguity_and_impossibility() {
+        // Only knowing where the 1 is leaves the other five wires unknown
+        let wirings = solve_wirings(segs("ab"));
+        assert_eq!(wirings.len(), 2 * 120);
+        assert!(matches!(
+            solve_wiring(segs("ab")),
+            Err(WiringErr::Ambiguous(w)) if w.len() == 240
+        ));
+        assert_eq!(
+            decode_all(&GlyphSet::decimal(), &wirings, segs("ab ba")),
+            Ok(vec![1, 1])
+        );
+
+        // Two different patterns can't both be a 1
+        assert_eq!(solve_wiring(segs("ab ac")), Err(WiringErr::Impossible));
+        assert_eq!(
+            solve_wiring(segs("abcdef abcdefg abcdeg abcdfg abcefg")),
+            Err(WiringErr::Impossible)
         );
-        output_sum += output;
     }
-    print!("P2: sum of outputs is {}", output_sum);
 }
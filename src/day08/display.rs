@@ -0,0 +1,159 @@
+use itertools::Itertools;
+
+use super::{AsSegments, Seg, Segments, Wiring};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum RenderStyle {
+    Ascii,
+    Unicode,
+}
+
+impl RenderStyle {
+    const fn fills(self) -> (char, char) {
+        match self {
+            Self::Ascii => ('#', '#'),
+            Self::Unicode => ('━', '┃'),
+        }
+    }
+}
+
+pub fn render(glyphs: impl IntoIterator<Item = impl AsSegments>, style: RenderStyle) -> String {
+    let (horiz, vert) = style.fills();
+    let mut rows = vec![String::new(); 7];
+    for (i, glyph) in glyphs.into_iter().enumerate() {
+        let glyph = glyph.as_segments();
+        let fill = |seg: Seg, c: char| if glyph.contains(seg.into()) { c } else { ' ' };
+        let h = |seg| format!(" {0}{0}{0} ", fill(seg, horiz));
+        let v = |l, r| format!("{}   {}", fill(l, vert), fill(r, vert));
+        let glyph_rows = [
+            h(Seg::A),
+            v(Seg::B, Seg::C),
+            v(Seg::B, Seg::C),
+            h(Seg::D),
+            v(Seg::E, Seg::F),
+            v(Seg::E, Seg::F),
+            h(Seg::G),
+        ];
+        for (row, part) in rows.iter_mut().zip(glyph_rows) {
+            if i > 0 {
+                row.push(' ');
+            }
+            row.push_str(&part);
+        }
+    }
+    rows.into_iter().map(|row| row + "\n").collect()
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct FaultModel {
+    wiring: Wiring,
+    stuck_on: Segments,
+    stuck_off: Segments,
+}
+
+impl FaultModel {
+    pub const fn healthy() -> Self {
+        FaultModel {
+            wiring: Wiring::identity(),
+            stuck_on: Segments::none(),
+            stuck_off: Segments::none(),
+        }
+    }
+
+    pub fn with_stuck_on(self, segs: impl AsSegments) -> Self {
+        FaultModel {
+            stuck_on: self.stuck_on | segs.as_segments(),
+            ..self
+        }
+    }
+
+    pub fn with_stuck_off(self, segs: impl AsSegments) -> Self {
+        FaultModel {
+            stuck_off: self.stuck_off | segs.as_segments(),
+            ..self
+        }
+    }
+
+    pub fn with_swapped(self, a: Seg, b: Seg) -> Self {
+        FaultModel {
+            wiring: self.wiring.swapped(a, b),
+            ..self
+        }
+    }
+
+    pub const fn wiring(&self) -> Wiring {
+        self.wiring
+    }
+
+    pub const fn stuck_on(&self) -> Segments {
+        self.stuck_on
+    }
+
+    pub const fn stuck_off(&self) -> Segments {
+        self.stuck_off
+    }
+
+    pub fn num_faults(&self) -> usize {
+        let miswired = Seg::values()
+            .iter()
+            .filter(|&&s| self.wiring.get(s) != s)
+            .count();
+        miswired + self.stuck_on.len() as usize + self.stuck_off.len() as usize
+    }
+
+    pub fn apply(&self, intended: impl AsSegments) -> Segments {
+        (self.wiring.apply(intended) | self.stuck_on) & !self.stuck_off
+    }
+}
+
+impl Default for FaultModel {
+    fn default() -> Self {
+        Self::healthy()
+    }
+}
+
+// Explains each observed reading of a known intended glyph with as few
+// faults as possible, returning every equally simple explanation
+pub fn diagnose(readings: &[(Segments, Segments)]) -> Vec<FaultModel> {
+    let mut best: Vec<FaultModel> = Vec::new();
+    for perm in Seg::values().iter().copied().permutations(7) {
+        let wiring = Wiring::new(perm.try_into().unwrap()).unwrap();
+        let mut model = FaultModel {
+            wiring,
+            ..FaultModel::healthy()
+        };
+        let consistent = Segments::all().into_iter().all(|seg| {
+            let seg: Segments = seg.into();
+            let mut matches = true;
+            let mut ever_on = false;
+            let mut ever_off = false;
+            for &(intended, observed) in readings {
+                let wired = wiring.apply(intended).contains(seg);
+                let lit = observed.contains(seg);
+                matches &= wired == lit;
+                ever_on |= lit;
+                ever_off |= !lit;
+            }
+            if matches {
+                true
+            } else if !ever_off {
+                model.stuck_on |= seg;
+                true
+            } else if !ever_on {
+                model.stuck_off |= seg;
+                true
+            } else {
+                false
+            }
+        });
+        if !consistent {
+            continue;
+        }
+        match best.first().map(FaultModel::num_faults) {
+            Some(n) if n < model.num_faults() => {}
+            Some(n) if n == model.num_faults() => best.push(model),
+            _ => best = vec![model],
+        }
+    }
+    best
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use super::{DisplayEntry, Seg};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum ParseSegErr {
+    InvalidChar(char),
+    InvalidIndex(u8),
+    WrongLength(usize),
+}
+
+impl Display for ParseSegErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidChar(c) => write!(f, "{:?} is not a segment letter (a-g)", c),
+            Self::InvalidIndex(i) => write!(f, "{} is not a segment index (0-6)", i),
+            Self::WrongLength(len) => {
+                write!(f, "expected a single segment letter, found {} chars", len)
+            }
+        }
+    }
+}
+
+impl Error for ParseSegErr {}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum ParseSegmentsErr {
+    InvalidChar(char),
+    Duplicate(Seg),
+}
+
+impl Display for ParseSegmentsErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidChar(c) => write!(f, "{:?} is not a segment letter (a-g)", c),
+            Self::Duplicate(seg) => write!(f, "segment {} appears more than once", seg),
+        }
+    }
+}
+
+impl Error for ParseSegmentsErr {}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum DisplayEntryParseError {
+    MissingDelimiter,
+    InvalidPattern(usize, ParseSegmentsErr),
+    InvalidOutput(usize, ParseSegmentsErr),
+    WrongPatternCount(usize),
+    WrongOutputCount(usize),
+}
+
+impl Display for DisplayEntryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingDelimiter => write!(f, "missing '|' between patterns and outputs"),
+            Self::InvalidPattern(i, err) => write!(f, "invalid pattern #{}: {}", i, err),
+            Self::InvalidOutput(i, err) => write!(f, "invalid output #{}: {}", i, err),
+            Self::WrongPatternCount(n) => write!(
+                f,
+                "expected {} patterns, found {}",
+                DisplayEntry::NUM_PATTERNS,
+                n
+            ),
+            Self::WrongOutputCount(n) => write!(
+                f,
+                "expected {} outputs, found {}",
+                DisplayEntry::NUM_OUTPUTS,
+                n
+            ),
+        }
+    }
+}
+
+impl Error for DisplayEntryParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidPattern(_, err) | Self::InvalidOutput(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
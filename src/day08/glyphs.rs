@@ -0,0 +1,87 @@
+use std::borrow::Borrow;
+
+use super::{AsSegments, Digit, Segments};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct GlyphSet {
+    glyphs: Vec<Segments>,
+}
+
+impl GlyphSet {
+    pub fn new(glyphs: impl IntoIterator<Item = impl AsSegments>) -> Self {
+        let mut set = GlyphSet { glyphs: Vec::new() };
+        for glyph in glyphs {
+            let glyph = glyph.as_segments();
+            if set.glyphs.contains(&glyph) {
+                panic!("duplicate glyph: {:?}", glyph);
+            }
+            set.glyphs.push(glyph);
+        }
+        set
+    }
+
+    pub fn decimal() -> Self {
+        Self::new(Digit::values())
+    }
+
+    pub fn hexadecimal() -> Self {
+        let letters = [
+            0b0111111, // A
+            0b1111010, // b
+            0b1010011, // C
+            0b1111100, // d
+            0b1011011, // E
+            0b0011011, // F
+        ];
+        Self::new(
+            Digit::values()
+                .map(|d| d.segments())
+                .into_iter()
+                .chain(letters.map(Segments::from_bits_masked)),
+        )
+    }
+
+    pub fn radix(&self) -> u32 {
+        self.glyphs.len() as u32
+    }
+
+    pub fn glyphs(&self) -> &[Segments] {
+        &self.glyphs
+    }
+
+    pub fn contains(&self, glyph: impl AsSegments) -> bool {
+        self.value_of(glyph).is_some()
+    }
+
+    pub fn value_of(&self, glyph: impl AsSegments) -> Option<u32> {
+        let glyph = glyph.as_segments();
+        self.glyphs
+            .iter()
+            .position(|&g| g == glyph)
+            .map(|i| i as u32)
+    }
+
+    pub fn segments_of(&self, value: u32) -> Option<Segments> {
+        self.glyphs.get(value as usize).copied()
+    }
+
+    pub fn parse(&self, glyphs: impl IntoIterator<Item = impl AsSegments>) -> Option<u64> {
+        let values = glyphs
+            .into_iter()
+            .map(|g| self.value_of(g))
+            .collect::<Option<Vec<_>>>()?;
+        parse_radix(values, self.radix())
+    }
+}
+
+pub fn parse_radix(values: impl IntoIterator<Item = impl Borrow<u32>>, radix: u32) -> Option<u64> {
+    let mut result: u64 = 0;
+    for v in values {
+        let v = *v.borrow();
+        if v >= radix {
+            return None;
+        }
+        result = result.checked_mul(radix as u64)?.checked_add(v as u64)?;
+    }
+    Some(result)
+}
@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use super::{AsSegments, Digit, GlyphSet, Seg, Segments};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Wiring([Seg; 7]);
+
+impl Wiring {
+    pub const fn identity() -> Self {
+        Wiring(*Seg::values())
+    }
+
+    pub fn new(map: [Seg; 7]) -> Option<Self> {
+        let targets: Segments = map.iter().collect();
+        if targets == Segments::all() {
+            Some(Wiring(map))
+        } else {
+            None
+        }
+    }
+
+    pub fn swapped(&self, a: Seg, b: Seg) -> Self {
+        let mut result = *self;
+        result.0.swap(a as usize, b as usize);
+        result
+    }
+
+    pub const fn get(&self, wire: Seg) -> Seg {
+        self.0[wire as usize]
+    }
+
+    pub fn apply(&self, observed: impl AsSegments) -> Segments {
+        observed.as_segments().map(|s| self.get(s))
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut inverse = *self;
+        for &wire in Seg::values() {
+            inverse.0[self.get(wire) as usize] = wire;
+        }
+        inverse
+    }
+
+    pub fn decode(&self, observed: impl AsSegments) -> Option<Digit> {
+        Digit::from_segments(self.apply(observed))
+    }
+
+    pub fn decode_with(&self, glyphs: &GlyphSet, observed: impl AsSegments) -> Option<u32> {
+        glyphs.value_of(self.apply(observed))
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum WiringErr {
+    Impossible,
+    Ambiguous(Vec<Wiring>),
+}
+
+impl Display for WiringErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Impossible => write!(f, "no wiring is consistent with the observed patterns"),
+            Self::Ambiguous(wirings) => write!(
+                f,
+                "{} different wirings are consistent with the observed patterns",
+                wirings.len()
+            ),
+        }
+    }
+}
+
+impl Error for WiringErr {}
+
+struct Solver<'a> {
+    glyphs: &'a GlyphSet,
+    patterns: Vec<Segments>,
+    solutions: Vec<Wiring>,
+}
+
+impl Solver<'_> {
+    // Narrows down the true segments each wire could be connected to, or
+    // returns None if some wire has nowhere left to go
+    fn propagate(&self, mut candidates: [Segments; 7]) -> Option<[Segments; 7]> {
+        for &pattern in &self.patterns {
+            let mut allowed_in = Segments::none();
+            let mut allowed_out = Segments::none();
+            for &glyph in self
+                .glyphs
+                .glyphs()
+                .iter()
+                .filter(|g| g.len() == pattern.len())
+            {
+                allowed_in |= glyph;
+                allowed_out |= !glyph;
+            }
+            for wire in Segments::all() {
+                candidates[wire as usize] &= if pattern.contains(wire.into()) {
+                    allowed_in
+                } else {
+                    allowed_out
+                };
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for wire in 0..7 {
+                match candidates[wire].len() {
+                    0 => return None,
+                    1 => {
+                        for other in (0..7).filter(|&o| o != wire) {
+                            let before = candidates[other];
+                            candidates[other] &= !candidates[wire];
+                            changed |= before != candidates[other];
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some(candidates)
+    }
+
+    fn is_valid(&self, wiring: &Wiring) -> bool {
+        self.patterns
+            .iter()
+            .all(|&p| self.glyphs.contains(wiring.apply(p)))
+    }
+
+    fn search(&mut self, candidates: [Segments; 7]) {
+        let candidates = match self.propagate(candidates) {
+            Some(c) => c,
+            None => return,
+        };
+
+        let undecided = (0..7)
+            .filter(|&w| candidates[w].len() > 1)
+            .min_by_key(|&w| candidates[w].len());
+        match undecided {
+            Some(wire) => {
+                for seg in candidates[wire] {
+                    let mut next = candidates;
+                    next[wire] = seg.into();
+                    self.search(next);
+                }
+            }
+            None => {
+                let wiring = Wiring(candidates.map(|c| c.into_iter().next().unwrap()));
+                if self.is_valid(&wiring) {
+                    self.solutions.push(wiring);
+                }
+            }
+        }
+    }
+}
+
+pub fn solve_wirings_with(
+    glyphs: &GlyphSet,
+    observed: impl IntoIterator<Item = impl AsSegments>,
+) -> Vec<Wiring> {
+    let mut patterns: Vec<Segments> = observed.into_iter().map(|s| s.as_segments()).collect();
+    patterns.sort_unstable_by_key(|s| (s.len(), s.bits()));
+    patterns.dedup();
+
+    let mut solver = Solver {
+        glyphs,
+        patterns,
+        solutions: Vec::new(),
+    };
+    solver.search([Segments::all(); 7]);
+    solver.solutions
+}
+
+pub fn solve_wirings(observed: impl IntoIterator<Item = impl AsSegments>) -> Vec<Wiring> {
+    solve_wirings_with(&GlyphSet::decimal(), observed)
+}
+
+pub fn solve_wiring(
+    observed: impl IntoIterator<Item = impl AsSegments>,
+) -> Result<Wiring, WiringErr> {
+    let mut wirings = solve_wirings(observed);
+    match wirings.len() {
+        0 => Err(WiringErr::Impossible),
+        1 => Ok(wirings.pop().unwrap()),
+        _ => Err(WiringErr::Ambiguous(wirings)),
+    }
+}
+
+pub fn decode_all(
+    glyphs: &GlyphSet,
+    wirings: &[Wiring],
+    observed: impl IntoIterator<Item = impl AsSegments>,
+) -> Result<Vec<u32>, WiringErr> {
+    let (first, rest) = wirings.split_first().ok_or(WiringErr::Impossible)?;
+    observed
+        .into_iter()
+        .map(|s| {
+            let digit = first.decode_with(glyphs, &s).ok_or(WiringErr::Impossible)?;
+            if rest
+                .iter()
+                .all(|w| w.decode_with(glyphs, &s) == Some(digit))
+            {
+                Ok(digit)
+            } else {
+                Err(WiringErr::Ambiguous(wirings.to_vec()))
+            }
+        })
+        .collect()
+}
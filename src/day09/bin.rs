@@ -1,5 +1,4 @@
 #![feature(nonzero_ops)]
-use std::collections::{HashSet, VecDeque};
 use std::fmt::{Debug, Display};
 use std::iter::FusedIterator;
 use std::num::NonZeroUsize;
@@ -7,18 +6,20 @@ use std::str::FromStr;
 
 use arrayvec::ArrayVec;
 
+mod watershed;
+
 #[derive(Clone, PartialEq, Eq)]
-pub struct Heightmap {
-    heights: Vec<u8>,
+pub struct Heightmap<H = u8> {
+    heights: Vec<H>,
     width: NonZeroUsize,
 }
 
-impl Heightmap {
+impl<H: Copy + Ord> Heightmap<H> {
     pub fn size(&self) -> (usize, usize) {
         (self.width.get(), self.height())
     }
 
-    pub fn try_get(&self, x: usize, y: usize) -> Option<u8> {
+    pub fn try_get(&self, x: usize, y: usize) -> Option<H> {
         if x >= self.width.get() {
             None
         } else {
@@ -26,11 +27,11 @@ impl Heightmap {
         }
     }
 
-    pub fn get(&self, x: usize, y: usize) -> u8 {
+    pub fn get(&self, x: usize, y: usize) -> H {
         self.try_get(x, y).unwrap()
     }
 
-    pub fn adjacent(&self, x: usize, y: usize) -> ArrayVec<((usize, usize), u8), 4> {
+    pub fn adjacent(&self, x: usize, y: usize) -> ArrayVec<((usize, usize), H), 4> {
         let mut adj = ArrayVec::new();
         let mut add_if_valid = |x, y| {
             if let (Some(x), Some(y)) = (x, y) {
@@ -46,6 +47,80 @@ impl Heightmap {
         adj
     }
 
+    pub fn is_low_point(&self, x: usize, y: usize) -> bool {
+        let height = self.get(x, y);
+        self.adjacent(x, y).into_iter().all(|(_, h)| height < h)
+    }
+
+    pub fn low_points(&self) -> impl Iterator<Item = ((usize, usize), H)> + '_ {
+        self.into_iter()
+            .filter(move |&((x, y), _)| self.is_low_point(x, y))
+    }
+
+    pub fn basins(&self, wall: H) -> Basins {
+        let (width, height) = self.size();
+        let is_open = |pos: usize| self.heights[pos] < wall;
+
+        let mut sets = UnionFind::new(self.heights.len());
+        for y in 0..height {
+            for x in 0..width {
+                let pos = self.get_pos(x, y);
+                if !is_open(pos) {
+                    continue;
+                }
+                if x > 0 && is_open(pos - 1) {
+                    sets.union(pos, pos - 1);
+                }
+                if y > 0 && is_open(pos - width) {
+                    sets.union(pos, pos - width);
+                }
+            }
+        }
+
+        let mut labels = vec![None; self.heights.len()];
+        let mut basins: Vec<Basin> = Vec::new();
+        let mut root_labels = vec![None; self.heights.len()];
+        for ((x, y), h) in self {
+            let pos = self.get_pos(x, y);
+            if !is_open(pos) {
+                continue;
+            }
+            let root = sets.find(pos);
+            let label = *root_labels[root].get_or_insert_with(|| {
+                basins.push(Basin {
+                    low_point: (x, y),
+                    size: 0,
+                    boundary: Vec::new(),
+                });
+                basins.len() - 1
+            });
+            labels[pos] = Some(label);
+
+            let basin = &mut basins[label];
+            basin.size += 1;
+            if h < self.get(basin.low_point.0, basin.low_point.1) {
+                basin.low_point = (x, y);
+            }
+            let on_edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            if on_edge || self.adjacent(x, y).iter().any(|&(_, h)| h >= wall) {
+                basin.boundary.push((x, y));
+            }
+        }
+
+        Basins {
+            labels,
+            width: self.width,
+            basins,
+        }
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(H) -> U) -> Heightmap<U> {
+        Heightmap {
+            heights: self.heights.iter().copied().map(f).collect(),
+            width: self.width,
+        }
+    }
+
     fn height(&self) -> usize {
         self.heights.len() / self.width
     }
@@ -55,7 +130,76 @@ impl Heightmap {
     }
 }
 
-impl Debug for Heightmap {
+struct UnionFind {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl UnionFind {
+    pub fn new(len: usize) -> Self {
+        UnionFind {
+            parents: (0..len).collect(),
+            sizes: vec![1; len],
+        }
+    }
+
+    pub fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    pub fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.sizes[a] < self.sizes[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parents[b] = a;
+        self.sizes[a] += self.sizes[b];
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basin {
+    pub low_point: (usize, usize),
+    pub size: usize,
+    pub boundary: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basins {
+    labels: Vec<Option<usize>>,
+    width: NonZeroUsize,
+    basins: Vec<Basin>,
+}
+
+impl Basins {
+    pub fn label_at(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width.get() {
+            None
+        } else {
+            self.labels.get(y * self.width.get() + x).copied().flatten()
+        }
+    }
+
+    pub fn basins(&self) -> &[Basin] {
+        &self.basins
+    }
+
+    pub fn largest(&self, n: usize) -> Vec<&Basin> {
+        let mut by_size: Vec<&Basin> = self.basins.iter().collect();
+        by_size.sort_by_key(|b| std::cmp::Reverse(b.size));
+        by_size.truncate(n);
+        by_size
+    }
+}
+
+impl<H: Debug> Debug for Heightmap<H> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Heightmap [")?;
         for chunk in self.heights.chunks_exact(self.width.get()) {
@@ -65,10 +209,10 @@ impl Debug for Heightmap {
     }
 }
 
-impl<'a> IntoIterator for &'a Heightmap {
-    type Item = ((usize, usize), u8);
+impl<'a, H: Copy> IntoIterator for &'a Heightmap<H> {
+    type Item = ((usize, usize), H);
 
-    type IntoIter = HeightmapIter<'a>;
+    type IntoIter = HeightmapIter<'a, H>;
 
     fn into_iter(self) -> Self::IntoIter {
         HeightmapIter { map: self, pos: 0 }
@@ -76,13 +220,13 @@ impl<'a> IntoIterator for &'a Heightmap {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct HeightmapIter<'a> {
-    map: &'a Heightmap,
+pub struct HeightmapIter<'a, H = u8> {
+    map: &'a Heightmap<H>,
     pos: usize,
 }
 
-impl<'a> Iterator for HeightmapIter<'a> {
-    type Item = ((usize, usize), u8);
+impl<'a, H: Copy> Iterator for HeightmapIter<'a, H> {
+    type Item = ((usize, usize), H);
 
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(height) = self.map.heights.get(self.pos) {
@@ -101,19 +245,24 @@ impl<'a> Iterator for HeightmapIter<'a> {
     }
 }
 
-impl ExactSizeIterator for HeightmapIter<'_> {
+impl<H: Copy> ExactSizeIterator for HeightmapIter<'_, H> {
     fn len(&self) -> usize {
         self.map.heights.len() - self.pos
     }
 }
 
-impl FusedIterator for HeightmapIter<'_> {}
+impl<H: Copy> FusedIterator for HeightmapIter<'_, H> {}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HeightmapParseError {
     InvalidDigit {
         byte: u8,
     },
+    InvalidHeight {
+        line: NonZeroUsize,
+        column: NonZeroUsize,
+        text: String,
+    },
     EmptyFirstLine,
     InconsistentWidths {
         line: NonZeroUsize,
@@ -132,6 +281,11 @@ impl Display for HeightmapParseError {
                     write!(f, "invalid digit 0x{:x}", byte)
                 }
             }
+            Self::InvalidHeight { line, column, text } => write!(
+                f,
+                "invalid height {:?} at line {}, column {}",
+                text, line, column
+            ),
             Self::EmptyFirstLine => write!(f, "first line was empty"),
             Self::InconsistentWidths {
                 line,
@@ -148,6 +302,58 @@ impl Display for HeightmapParseError {
 
 impl std::error::Error for HeightmapParseError {}
 
+impl<H: FromStr> Heightmap<H> {
+    pub fn parse_whitespace(s: &str) -> Result<Self, HeightmapParseError> {
+        Self::parse_fields(s, |line| line.split_whitespace().collect())
+    }
+
+    pub fn parse_csv(s: &str) -> Result<Self, HeightmapParseError> {
+        Self::parse_fields(s, |line| line.split(',').map(str::trim).collect())
+    }
+
+    fn parse_fields(
+        s: &str,
+        split: impl Fn(&str) -> Vec<&str>,
+    ) -> Result<Self, HeightmapParseError> {
+        let one = NonZeroUsize::new(1).unwrap();
+
+        // Blank lines (such as a trailing one) don't hold any rows
+        let mut rows = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (one.saturating_add(i), split(line)));
+        let (first_line, first_row) = rows.next().ok_or(HeightmapParseError::EmptyFirstLine)?;
+        let width: NonZeroUsize = first_row
+            .len()
+            .try_into()
+            .or(Err(HeightmapParseError::EmptyFirstLine))?;
+        let mut heights = Vec::new();
+
+        for (line, row) in std::iter::once((first_line, first_row)).chain(rows) {
+            if row.len() != width.get() {
+                return Err(HeightmapParseError::InconsistentWidths {
+                    line,
+                    expected: width,
+                    actual: row.len(),
+                });
+            }
+            for (i, field) in row.into_iter().enumerate() {
+                let height = field
+                    .parse()
+                    .map_err(|_| HeightmapParseError::InvalidHeight {
+                        line,
+                        column: one.saturating_add(i),
+                        text: field.to_string(),
+                    })?;
+                heights.push(height);
+            }
+        }
+
+        Ok(Heightmap { heights, width })
+    }
+}
+
 impl FromStr for Heightmap {
     type Err = HeightmapParseError;
 
@@ -195,55 +401,182 @@ impl FromStr for Heightmap {
 pub fn main() {
     let heights: Heightmap = include_str!("input.txt").parse().unwrap();
 
-    let mut risk_sum = 0;
-
-    let mut max_basins = [0usize; 3];
-    let mut basin = HashSet::new();
-    let mut to_visit = VecDeque::new();
-
-    for ((x, y), height) in &heights {
-        let min_adjacent = heights
-            .adjacent(x, y)
-            .into_iter()
-            .map(|(_, h)| h)
-            .min()
-            .unwrap();
-        if height < min_adjacent {
-            risk_sum += height as u32 + 1;
-
-            // Flood fill
-            basin.clear();
-            basin.insert((x, y));
-            to_visit.clear();
-            to_visit.push_back((x, y));
-
-            while let Some((x, y)) = to_visit.pop_front() {
-                for ((x, y), h) in heights.adjacent(x, y) {
-                    if h < 9 && basin.insert((x, y)) {
-                        to_visit.push_back((x, y));
-                    }
-                }
-            }
-
-            println!("({}, {}) -> {} {:?}", x, y, basin.len(), basin);
+    let risk_sum: u32 = heights.low_points().map(|(_, h)| h as u32 + 1).sum();
 
-            let (min_i, min_len) = max_basins
-                .iter()
-                .copied()
-                .enumerate()
-                .min_by_key(|(_, l)| *l)
-                .unwrap();
-            if min_len < basin.len() {
-                max_basins[min_i] = basin.len();
-            }
-        }
-    }
-
-    let max_basins_product: usize = max_basins.into_iter().product();
+    let basins = heights.basins(9);
+    let max_basins: Vec<usize> = basins.largest(3).iter().map(|b| b.size).collect();
+    let max_basins_product: usize = max_basins.iter().product();
 
     println!("P1: risk sum is {}", risk_sum);
     println!(
         "P2: largest basins are {:?} = {}",
         max_basins, max_basins_product
     );
+
+    let drainage = heights.drainage();
+    let mut sizes = drainage.basin_sizes();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    println!(
+        "Drainage: {} basins, largest {:?}",
+        sizes.len(),
+        &sizes[..3]
+    );
+    let ((x, y), _) = heights
+        .into_iter()
+        .max_by_key(|&((x, y), _)| drainage.accumulation(x, y))
+        .unwrap();
+    let sink = drainage.sinks().nth(drainage.basin_of(x, y)).unwrap();
+    println!(
+        "Most water passes through ({}, {}): {} cells, draining to {:?}",
+        x,
+        y,
+        drainage.accumulation(x, y),
+        sink
+    );
+    for level in 1..=9 {
+        println!(
+            "Water at {}: {} cells flooded in {} lakes, volume {}",
+            level,
+            heights.flooded(level).count(),
+            heights.lakes(level).basins().len(),
+            heights.flood_volume(level)
+        );
+    }
+
+    let elevations: Heightmap<i32> = Heightmap::parse_whitespace(ELEVATIONS).unwrap();
+    let below_sea = elevations.lakes(0);
+    println!(
+        "Elevations: low points {:?}, {} areas below sea level, volume {}",
+        elevations.low_points().collect::<Vec<_>>(),
+        below_sea.basins().len(),
+        elevations.flood_volume(0)
+    );
+    let csv: Heightmap<u32> =
+        Heightmap::parse_csv(&to_csv(&heights.map(|h| h as u32 * 125))).unwrap();
+    println!(
+        "Scaled to metres: {} basins, volume at 1000m {}",
+        csv.basins(9 * 125).basins().len(),
+        csv.flood_volume(1000)
+    );
+}
+
+const ELEVATIONS: &str = "
+     120    85   -12   -30    40
+      96    14    -4   -18    75
+     210   180    35     2   -61
+";
+
+fn to_csv(map: &Heightmap<u32>) -> String {
+    let (width, _) = map.size();
+    let mut csv = String::new();
+    for ((x, _), h) in map {
+        csv += &h.to_string();
+        csv.push(if x + 1 == width { '\n' } else { ',' });
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_map() -> Heightmap {
+        include_str!("test.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn finds_low_points() {
+        let low_points: Vec<_> = test_map().low_points().collect();
+        assert_eq!(
+            low_points,
+            [((1, 0), 1), ((9, 0), 0), ((2, 2), 5), ((6, 4), 5)]
+        );
+    }
+
+    #[test]
+    fn labels_basins() {
+        let map = test_map();
+        let basins = map.basins(9);
+        let sizes: Vec<usize> = basins.basins().iter().map(|b| b.size).collect();
+        assert_eq!(sizes, [3, 9, 14, 9]);
+        assert_eq!(basins.label_at(0, 0), Some(0));
+        assert_eq!(basins.label_at(2, 0), None);
+        assert_eq!(basins.basins()[1].low_point, (9, 0));
+        for basin in basins.basins() {
+            let (x, y) = basin.low_point;
+            assert!(map.is_low_point(x, y));
+        }
+
+        // Without any walls the whole map is one basin, bounded by the map
+        // edges
+        let basins = map.basins(10);
+        assert_eq!(basins.basins().len(), 1);
+        assert_eq!(basins.basins()[0].size, 50);
+        assert_eq!(basins.basins()[0].boundary.len(), 26);
+    }
+
+    #[test]
+    fn drains_to_low_points() {
+        let map = test_map();
+        let drainage = map.drainage();
+        let sinks: Vec<_> = drainage.sinks().collect();
+        let low_points: Vec<_> = map.low_points().map(|(pos, _)| pos).collect();
+        assert_eq!(sinks.len(), low_points.len());
+        assert!(sinks.iter().all(|p| low_points.contains(p)));
+        assert_eq!(drainage.basin_sizes().iter().sum::<u32>(), 50);
+
+        assert_eq!(drainage.receiver(0, 0), Some((1, 0)));
+        assert_eq!(drainage.receiver(1, 0), None);
+        assert_eq!(drainage.accumulation(0, 2), 1);
+        assert_eq!(drainage.accumulation(0, 1), 2);
+        for (x, y) in sinks {
+            assert_eq!(drainage.sinks().nth(drainage.basin_of(x, y)), Some((x, y)));
+        }
+    }
+
+    #[test]
+    fn floods_to_level() {
+        let map = test_map();
+        assert_eq!(map.flooded(0).count(), 0);
+        assert_eq!(map.flooded(1).collect::<Vec<_>>(), [(9, 0)]);
+        assert_eq!(map.flood_volume(2), 2 + 1 + 1 + 1);
+        assert_eq!(map.flooded(10).count(), 50);
+        assert_eq!(map.lakes(10).basins().len(), 1);
+    }
+
+    #[test]
+    fn parses_integer_heights() {
+        let map = test_map();
+        let spaced = include_str!("test.txt").replace("", " ");
+        let parsed: Heightmap = Heightmap::parse_whitespace(&spaced).unwrap();
+        assert_eq!(parsed, map);
+        let scaled = map.map(|h| h as u32 * 125);
+        assert_eq!(Heightmap::parse_csv(&to_csv(&scaled)), Ok(scaled.clone()));
+        assert_eq!(
+            scaled.low_points().map(|(pos, _)| pos).collect::<Vec<_>>(),
+            map.low_points().map(|(pos, _)| pos).collect::<Vec<_>>()
+        );
+        assert_eq!(scaled.basins(9 * 125), map.basins(9));
+
+        let deep: Heightmap<i64> = Heightmap::parse_csv("-5, 300\n-40000000000, 7").unwrap();
+        assert_eq!(deep.get(0, 1), -40000000000);
+        assert_eq!(
+            deep.low_points().collect::<Vec<_>>(),
+            [((0, 1), -40000000000)]
+        );
+        assert_eq!(deep.flood_volume(0), 40000000005);
+
+        assert_eq!(
+            Heightmap::<u8>::parse_whitespace("1 2\n3 x"),
+            Err(HeightmapParseError::InvalidHeight {
+                line: NonZeroUsize::new(2).unwrap(),
+                column: NonZeroUsize::new(2).unwrap(),
+                text: "x".to_string(),
+            })
+        );
+        assert!(matches!(
+            Heightmap::<u8>::parse_csv("1,2\n3"),
+            Err(HeightmapParseError::InconsistentWidths { actual: 1, .. })
+        ));
+    }
 }
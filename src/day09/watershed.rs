@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+
+use super::{Basins, Heightmap};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drainage {
+    width: NonZeroUsize,
+    receivers: Vec<Option<usize>>,
+    labels: Vec<usize>,
+    sinks: Vec<usize>,
+    accumulation: Vec<u32>,
+}
+
+impl Drainage {
+    pub fn new<H: Copy + Ord>(map: &Heightmap<H>) -> Self {
+        let len = map.heights.len();
+        let width = map.width;
+        let to_xy = |pos: usize| (pos % width, pos / width);
+        let neighbours = |pos: usize| {
+            let (x, y) = to_xy(pos);
+            map.adjacent(x, y)
+                .into_iter()
+                .map(|((x, y), h)| (map.get_pos(x, y), h))
+        };
+
+        // Water runs to the lowest strictly lower neighbour
+        let mut receivers: Vec<Option<usize>> = (0..len)
+            .map(|pos| {
+                neighbours(pos)
+                    .filter(|&(_, h)| h < map.heights[pos])
+                    .min_by_key(|&(_, h)| h)
+                    .map(|(n, _)| n)
+            })
+            .collect();
+
+        // On flat ground, water runs towards the nearest way down. Flat areas
+        // with no way down collect into a single sink.
+        let mut queue: VecDeque<usize> = (0..len).filter(|&p| receivers[p].is_some()).collect();
+        let mut sinks = Vec::new();
+        let mut is_sink = vec![false; len];
+        let mut next_unvisited = 0;
+        loop {
+            while let Some(pos) = queue.pop_front() {
+                for (n, h) in neighbours(pos) {
+                    if h == map.heights[pos] && receivers[n].is_none() && !is_sink[n] {
+                        receivers[n] = Some(pos);
+                        queue.push_back(n);
+                    }
+                }
+            }
+            while next_unvisited < len
+                && (receivers[next_unvisited].is_some() || is_sink[next_unvisited])
+            {
+                next_unvisited += 1;
+            }
+            if next_unvisited == len {
+                break;
+            }
+            sinks.push(next_unvisited);
+            is_sink[next_unvisited] = true;
+            queue.push_back(next_unvisited);
+        }
+
+        // Distance downstream to the sink, so upstream cells can be handled first
+        let mut labels = vec![usize::MAX; len];
+        let mut depths = vec![0; len];
+        for (label, &sink) in sinks.iter().enumerate() {
+            labels[sink] = label;
+        }
+        let mut path = Vec::new();
+        for start in 0..len {
+            let mut pos = start;
+            while labels[pos] == usize::MAX {
+                path.push(pos);
+                pos = receivers[pos].unwrap();
+            }
+            let (label, mut depth) = (labels[pos], depths[pos]);
+            for &p in path.iter().rev() {
+                depth += 1;
+                labels[p] = label;
+                depths[p] = depth;
+            }
+            path.clear();
+        }
+
+        let mut order: Vec<usize> = (0..len).collect();
+        order.sort_unstable_by_key(|&p| std::cmp::Reverse(depths[p]));
+        let mut accumulation = vec![1; len];
+        for pos in order {
+            if let Some(r) = receivers[pos] {
+                accumulation[r] += accumulation[pos];
+            }
+        }
+
+        Drainage {
+            width,
+            receivers,
+            labels,
+            sinks,
+            accumulation,
+        }
+    }
+
+    fn to_xy(&self, pos: usize) -> (usize, usize) {
+        (pos % self.width, pos / self.width)
+    }
+
+    fn get_pos(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width.get(), "x out of range");
+        y * self.width.get() + x
+    }
+
+    pub fn receiver(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        self.receivers[self.get_pos(x, y)].map(|p| self.to_xy(p))
+    }
+
+    pub fn basin_of(&self, x: usize, y: usize) -> usize {
+        self.labels[self.get_pos(x, y)]
+    }
+
+    pub fn sinks(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.sinks.iter().map(|&p| self.to_xy(p))
+    }
+
+    pub fn basin_sizes(&self) -> Vec<u32> {
+        self.sinks.iter().map(|&p| self.accumulation[p]).collect()
+    }
+
+    pub fn accumulation(&self, x: usize, y: usize) -> u32 {
+        self.accumulation[self.get_pos(x, y)]
+    }
+}
+
+impl<H: Copy + Ord> Heightmap<H> {
+    pub fn drainage(&self) -> Drainage {
+        Drainage::new(self)
+    }
+
+    pub fn flooded(&self, level: H) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.into_iter()
+            .filter(move |&(_, h)| h < level)
+            .map(|(pos, _)| pos)
+    }
+
+    // Every connected area below the water level holds its own lake
+    pub fn lakes(&self, level: H) -> Basins {
+        self.basins(level)
+    }
+
+    pub fn flood_volume(&self, level: H) -> u128
+    where
+        H: Into<i128>,
+    {
+        self.into_iter()
+            .filter(|&(_, h)| h < level)
+            .map(|(_, h)| (level.into() - h.into()) as u128)
+            .sum()
+    }
+}
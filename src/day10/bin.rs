@@ -1,5 +1,14 @@
 use std::fmt::{Display, Write};
 
+mod lint;
+use lint::{apply_fixes, lint, lint_with, Recovery};
+mod nesting;
+use nesting::{parse_with, Alphabet, Standard};
+mod set;
+use set::BracketSet;
+mod stream;
+use stream::BracketChecker;
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum BracketShape {
@@ -10,6 +19,10 @@ pub enum BracketShape {
 }
 
 impl BracketShape {
+    pub const fn values() -> [Self; 4] {
+        [Self::Round, Self::Square, Self::Curly, Self::Angle]
+    }
+
     pub const fn open(&self) -> Bracket {
         Bracket(*self, BracketSide::Open)
     }
@@ -19,6 +32,21 @@ impl BracketShape {
     }
 }
 
+pub trait Delimiter: Copy + Eq {
+    fn open_char(&self) -> char;
+    fn close_char(&self) -> char;
+}
+
+impl Delimiter for BracketShape {
+    fn open_char(&self) -> char {
+        self.open().get_char()
+    }
+
+    fn close_char(&self) -> char {
+        self.close().get_char()
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum BracketSide {
@@ -113,41 +141,38 @@ impl Display for BracketCharErr {
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub enum ParseBracketsErr {
-    Unclosed(Vec<BracketShape>),
-    IncorrectClose {
-        expected: BracketShape,
-        actual: BracketShape,
-    },
-    UnexpectedClose(BracketShape),
+pub enum ParseBracketsErr<S = BracketShape> {
+    Unclosed(Vec<S>),
+    IncorrectClose { expected: S, actual: S },
+    UnexpectedClose(S),
     InvalidBracket(char),
 }
 
-impl std::error::Error for ParseBracketsErr {}
+impl<S: Delimiter + std::fmt::Debug> std::error::Error for ParseBracketsErr<S> {}
 
-impl Display for ParseBracketsErr {
+impl<S: Delimiter> Display for ParseBracketsErr<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseBracketsErr::Unclosed(shapes) => Ok({
                 write!(f, "code ended without closing the ")?;
                 for shape in shapes {
-                    write!(f, "{}", shape.open())?;
+                    write!(f, "{}", shape.open_char())?;
                 }
             }),
             ParseBracketsErr::IncorrectClose { expected, actual } => {
                 write!(
                     f,
                     "code closed the {} with a {}",
-                    expected.open(),
-                    actual.close(),
+                    expected.open_char(),
+                    actual.close_char(),
                 )
             }
             ParseBracketsErr::UnexpectedClose(shape) => {
                 write!(
                     f,
                     "code contained {} with no matching {}",
-                    shape.close(),
-                    shape.open(),
+                    shape.close_char(),
+                    shape.open_char(),
                 )
             }
             ParseBracketsErr::InvalidBracket(bracket) => {
@@ -168,32 +193,73 @@ impl From<BracketCharErr> for ParseBracketsErr {
     }
 }
 
-pub fn parse_brackets(text: &str) -> Result<(), ParseBracketsErr> {
-    let mut open = vec![];
-    for bracket in text.chars() {
-        match bracket.try_into()? {
-            Bracket(shape, BracketSide::Open) => open.push(shape),
-            Bracket(shape, BracketSide::Close) => {
-                if let Some(expected) = open.pop() {
-                    if expected != shape {
-                        return Err(ParseBracketsErr::IncorrectClose {
-                            expected,
-                            actual: shape,
-                        });
-                    }
-                } else {
-                    return Err(ParseBracketsErr::UnexpectedClose(shape));
-                }
-            }
-        }
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Completion<S = BracketShape> {
+    unclosed: Vec<S>,
+}
+
+impl<S: Delimiter> Completion<S> {
+    pub fn is_empty(&self) -> bool {
+        self.unclosed.is_empty()
+    }
+
+    pub fn closing(&self) -> impl Iterator<Item = char> + '_ {
+        self.unclosed.iter().rev().map(|delim| delim.close_char())
+    }
+
+    // None if the score doesn't fit, or a delimiter has no value
+    pub fn score_with(&self, value: impl Fn(S) -> Option<u64>) -> Option<u128> {
+        self.unclosed.iter().rev().try_fold(0u128, |score, &delim| {
+            score.checked_mul(5)?.checked_add(value(delim)? as u128)
+        })
+    }
+}
+
+impl Completion {
+    pub fn score(&self) -> Option<u128> {
+        self.score_with(|shape| Some(get_autocomplete_value(shape)))
+    }
+}
+
+impl<S> Default for Completion<S> {
+    fn default() -> Self {
+        Completion { unclosed: vec![] }
     }
-    if open.is_empty() {
-        Ok(())
-    } else {
-        Err(ParseBracketsErr::Unclosed(open))
+}
+
+impl<S: Delimiter> Display for Completion<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.closing().try_for_each(|c| f.write_char(c))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct EvenCountErr(pub usize);
+
+impl std::error::Error for EvenCountErr {}
+
+impl Display for EvenCountErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} scores have no single middle score", self.0)
     }
 }
 
+pub fn middle_score<T: Ord>(scores: impl IntoIterator<Item = T>) -> Result<T, EvenCountErr> {
+    let mut scores: Vec<T> = scores.into_iter().collect();
+    if scores.len().is_multiple_of(2) {
+        return Err(EvenCountErr(scores.len()));
+    }
+    let middle = scores.len() / 2;
+    scores.select_nth_unstable(middle);
+    Ok(scores.swap_remove(middle))
+}
+
+// Code that's only missing closes is fine, and gets the completion for them
+pub fn parse_brackets(text: &str) -> Result<Completion, ParseBracketsErr> {
+    let unclosed = parse_with(&Standard, text)?;
+    Ok(Completion { unclosed })
+}
+
 pub const fn get_syntax_error_value(shape: BracketShape) -> u64 {
     match shape {
         BracketShape::Round => 3,
@@ -217,25 +283,413 @@ pub fn main() {
     let mut autocomplete_scores = vec![];
 
     for line in include_str!("input.txt").lines() {
-        match parse_brackets(line).unwrap_err() {
-            ParseBracketsErr::IncorrectClose { actual, .. } => {
+        match parse_brackets(line) {
+            // Lines that are already complete have nothing to score
+            Ok(completion) if completion.is_empty() => {}
+            Ok(completion) => match completion.score() {
+                Some(score) => autocomplete_scores.push(score),
+                None => println!("Skipping {:?}: autocomplete score too large", line),
+            },
+            Err(ParseBracketsErr::IncorrectClose { actual, .. }) => {
                 syntax_error_score += get_syntax_error_value(actual);
             }
-            ParseBracketsErr::Unclosed(unclosed) => {
-                let mut autocomplete_score = 0;
-                for shape in unclosed.into_iter().rev() {
-                    autocomplete_score *= 5;
-                    autocomplete_score += get_autocomplete_value(shape);
-                }
-                autocomplete_scores.push(autocomplete_score);
-            }
-            err => panic!("{}", err),
+            Err(err) => panic!("{}", err),
         }
     }
 
-    autocomplete_scores.sort();
-    let autocomplete_score = autocomplete_scores[autocomplete_scores.len() / 2];
+    let autocomplete_score = middle_score(autocomplete_scores).unwrap();
 
     println!("P1: syntax error score = {}", syntax_error_score);
     println!("P2: autocomplete score = {}", autocomplete_score);
+
+    let input = include_str!("input.txt");
+    for recovery in [Recovery::Replace, Recovery::Delete, Recovery::CloseToMatch] {
+        let mut num_errors = 0;
+        let mut num_fixed = 0;
+        for line in input.lines() {
+            let errors = lint(line, recovery);
+            num_errors += errors.len();
+            if parse_brackets(&apply_fixes(line, &errors)).is_ok_and(|c| c.is_empty()) {
+                num_fixed += 1;
+            }
+        }
+        println!(
+            "Lint ({:?}): {} errors, {}/{} lines fixed",
+            recovery,
+            num_errors,
+            num_fixed,
+            input.lines().count()
+        );
+    }
+    let first_line = input.lines().next().unwrap();
+    for err in lint(first_line, Recovery::CloseToMatch) {
+        println!("  {}", err);
+    }
+
+    let brackets: BracketSet = include_str!("brackets.txt").parse().unwrap();
+    let mut configured_score = 0;
+    let mut configured_autocomplete = vec![];
+    for line in input.lines() {
+        match brackets.parse(line) {
+            Err(ParseBracketsErr::IncorrectClose { actual, .. }) => {
+                configured_score += brackets.scores(actual).unwrap().syntax_error;
+            }
+            Ok(completion) if !completion.is_empty() => {
+                configured_autocomplete.extend(brackets.autocomplete_score(&completion));
+            }
+            _ => {}
+        }
+    }
+    println!(
+        "Configured brackets ({} pairs, {}): syntax error score = {}, autocomplete score = {}",
+        brackets.pairs().count(),
+        if brackets == BracketSet::standard() {
+            "standard"
+        } else {
+            "custom"
+        },
+        configured_score,
+        middle_score(configured_autocomplete).unwrap()
+    );
+
+    let mut checker = BracketChecker::per_line();
+    let mut streamed_score = 0;
+    let mut line_ends = input.match_indices('\n').map(|(i, _)| i as u64);
+    let mut line_end = line_ends.next();
+    let mut scored_line = None;
+    checker
+        .check_reader(input.as_bytes(), |err| {
+            while line_end.is_some_and(|end| err.offset > end) {
+                line_end = line_ends.next();
+            }
+            // Only the first error on each line counts towards the score
+            if scored_line != Some(line_end) {
+                scored_line = Some(line_end);
+                if let ParseBracketsErr::IncorrectClose { actual, .. } = err.err {
+                    streamed_score += get_syntax_error_value(actual);
+                }
+            }
+        })
+        .unwrap();
+    println!("Streamed: syntax error score = {}", streamed_score);
+
+    let mut checker = BracketChecker::new();
+    let (head, tail) = first_line.split_at(first_line.len() / 2);
+    checker.feed(head.as_bytes(), |_| {});
+    let snapshot = checker.snapshot();
+    let mut num_errors = 0;
+    checker.feed(tail.as_bytes(), |_| num_errors += 1);
+    println!(
+        "Snapshot at byte {} (depth {}), then {} errors by byte {} (depth {})",
+        snapshot.offset(),
+        snapshot.depth(),
+        num_errors,
+        checker.offset(),
+        checker.depth()
+    );
+    checker.restore(&snapshot);
+    checker.finish(|err| println!("  after restoring: {}", err));
+
+    let quotes: BracketSet = QUOTES.parse().unwrap();
+    for text in ["«'\"\"'»", "'«'»", "«\"»", "«'\""] {
+        match quotes.parse(text) {
+            Ok(completion) if completion.is_empty() => println!("{}: ok", text),
+            Ok(completion) => println!(
+                "{}: needs {} (score {})",
+                text,
+                completion,
+                quotes.autocomplete_score(&completion).unwrap()
+            ),
+            Err(err) => println!("{}: {}", text, err),
+        }
+    }
+    let quoted = "«'\"»'\n'«»";
+    for line in quoted.lines() {
+        let errors = lint_with(&quotes, line, Recovery::CloseToMatch);
+        println!("{} fixed to {}", line, apply_fixes(line, &errors));
+    }
+    let mut checker = BracketChecker::with_alphabet(quotes).split_lines();
+    checker
+        .check_reader(quoted.as_bytes(), |err| println!("  streamed: {}", err))
+        .unwrap();
+}
+
+const QUOTES: &str = "
+« » 10 1
+' ' 20 2
+\" \" 30 3
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use set::Pair;
+
+    #[test]
+    fn lint_finds_first_error() {
+        for line in include_str!("test.txt").lines() {
+            let errors = lint(line, Recovery::Replace);
+            let first = errors.first().unwrap();
+            match parse_brackets(line) {
+                Ok(completion) => {
+                    assert_eq!(errors.len(), 1);
+                    assert_eq!(
+                        first.fix,
+                        lint::Fix::Insert {
+                            column: line.len(),
+                            text: completion.to_string()
+                        }
+                    );
+                }
+                Err(err) => assert_eq!(err, first.err),
+            }
+        }
+    }
+
+    #[test]
+    fn lint_reports_every_error() {
+        let errors = lint("(]x)>{<", Recovery::Replace);
+        let columns: Vec<_> = errors.iter().map(|e| (e.column, e.opener)).collect();
+        assert_eq!(
+            columns,
+            [(1, Some(0)), (2, None), (3, None), (4, None), (7, Some(5))]
+        );
+        assert_eq!(
+            errors[4].err,
+            ParseBracketsErr::Unclosed(vec![BracketShape::Curly, BracketShape::Angle])
+        );
+        assert_eq!(apply_fixes("(]x)>{<", &errors), "(){<>}");
+
+        let errors = lint("{(<}", Recovery::CloseToMatch);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].opener, Some(2));
+        assert_eq!(apply_fixes("{(<}", &errors), "{(<>)}");
+        // Nothing matches the ], so it's replaced before closing to the {
+        let errors = lint("{(<]}", Recovery::CloseToMatch);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(apply_fixes("{(<]}", &errors), "{(<>)}");
+        let errors = lint("[(<]", Recovery::CloseToMatch);
+        assert_eq!(apply_fixes("[(<]", &errors), "[(<>)]");
+        let errors = lint("[(<]", Recovery::Delete);
+        assert_eq!(apply_fixes("[(<]", &errors), "[(<>)]");
+    }
+
+    #[test]
+    fn configured_brackets() {
+        let standard = BracketSet::standard();
+        assert_eq!(include_str!("brackets.txt").parse(), Ok(standard.clone()));
+        for line in include_str!("test.txt").lines() {
+            match (parse_brackets(line), standard.parse(line)) {
+                (
+                    Err(ParseBracketsErr::IncorrectClose { expected, actual }),
+                    Err(ParseBracketsErr::IncorrectClose {
+                        expected: configured_expected,
+                        actual: configured_actual,
+                    }),
+                ) => {
+                    assert_eq!(configured_expected, expected.into());
+                    assert_eq!(configured_actual, actual.into());
+                }
+                (Ok(completion), Ok(configured)) => {
+                    assert_eq!(configured.to_string(), completion.to_string());
+                    assert_eq!(standard.autocomplete_score(&configured), completion.score());
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        let quotes: BracketSet = QUOTES.parse().unwrap();
+        let quote = Pair::new('\'', '\'');
+        assert_eq!(quotes.parse("«''»'\"\"'"), Ok(Completion::default()));
+        assert_eq!(
+            quotes.parse("'«'"),
+            Ok(Completion {
+                unclosed: vec![quote, Pair::new('«', '»'), quote]
+            })
+        );
+        assert_eq!(
+            quotes.parse("»"),
+            Err(ParseBracketsErr::UnexpectedClose(Pair::new('«', '»')))
+        );
+        assert_eq!(
+            "( ) 1 2\n[ ( 3 4".parse::<BracketSet>(),
+            Err(set::BracketSetErr::DuplicateChar('('))
+        );
+        assert!(matches!(
+            "<> 1 2".parse::<BracketSet>(),
+            Err(set::BracketSetErr::WrongFieldCount { line: 1, count: 3 })
+        ));
+    }
+
+    #[test]
+    fn scores_configured_completions() {
+        let quotes: BracketSet = QUOTES.parse().unwrap();
+        let completion = quotes.parse("«'\"").unwrap();
+        assert_eq!(completion.to_string(), "\"'»");
+        // " is worth 3, ' 2 and » 1
+        assert_eq!(
+            quotes.autocomplete_score(&completion),
+            Some((3 * 5 + 2) * 5 + 1)
+        );
+        assert_eq!(BracketSet::standard().autocomplete_score(&completion), None);
+        assert_eq!(quotes.autocomplete_score(&Completion::default()), Some(0));
+    }
+
+    #[test]
+    fn streams_in_chunks() {
+        let input = include_str!("input.txt");
+        let mut expected = vec![];
+        let mut line_start = 0;
+        for line in input.lines() {
+            for err in lint(line, Recovery::Replace) {
+                expected.push((line_start + err.column as u64, err.err));
+            }
+            line_start += line.len() as u64 + 1;
+        }
+
+        for chunk_size in [1, 7, 64, input.len()] {
+            let mut checker = BracketChecker::per_line();
+            let mut errors = vec![];
+            for chunk in input.as_bytes().chunks(chunk_size) {
+                checker.feed(chunk, |err| errors.push((err.offset, err.err)));
+            }
+            checker.finish(|err| errors.push((err.offset, err.err)));
+            assert_eq!(errors, expected);
+        }
+    }
+
+    #[test]
+    fn checker_snapshots() {
+        let mut checker = BracketChecker::new();
+        let mut errors = vec![];
+        checker.feed("{(é".as_bytes(), |err| errors.push(err));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].offset, 2);
+        assert_eq!(errors[0].err, ParseBracketsErr::InvalidBracket('é'));
+
+        let snapshot = checker.snapshot();
+        assert_eq!((snapshot.offset(), snapshot.depth()), (4, 2));
+        checker.feed(b"]]", |err| errors.push(err));
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[1].opener, Some(1));
+
+        errors.clear();
+        checker.restore(&snapshot);
+        checker.feed(b")}", |err| errors.push(err));
+        checker.finish(|err| errors.push(err));
+        assert_eq!(errors, []);
+        assert_eq!(checker.depth(), 0);
+
+        // A char cut short by the end of the input is still reported
+        checker.feed(&[b'(', 0xe2, 0x80], |err| errors.push(err));
+        checker.finish(|err| errors.push(err));
+        let errors: Vec<_> = errors.into_iter().map(|e| (e.offset, e.err)).collect();
+        assert_eq!(
+            errors,
+            [
+                (1, ParseBracketsErr::InvalidBracket('\u{fffd}')),
+                (3, ParseBracketsErr::Unclosed(vec![BracketShape::Round]))
+            ]
+        );
+    }
+
+    #[test]
+    fn configured_lint_and_stream() {
+        let quotes: BracketSet = QUOTES.parse().unwrap();
+        let (quote, guillemets) = (Pair::new('\'', '\''), Pair::new('«', '»'));
+        let errors = lint_with(&quotes, "«'»", Recovery::Replace);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.column, e.opener))
+                .collect::<Vec<_>>(),
+            [(2, Some(1)), (3, Some(0))]
+        );
+        assert_eq!(
+            errors[0].err,
+            ParseBracketsErr::IncorrectClose {
+                expected: quote,
+                actual: guillemets
+            }
+        );
+        assert_eq!(apply_fixes("«'»", &errors), "«''»");
+        assert_eq!(
+            quotes.parse(&apply_fixes("«'»", &errors)),
+            Ok(Completion::default())
+        );
+
+        // Feeding a byte at a time splits the guillemets across chunks
+        let mut checker = BracketChecker::with_alphabet(quotes);
+        let mut streamed = vec![];
+        for byte in "«'»".bytes() {
+            checker.feed(&[byte], |err| {
+                streamed.push((err.offset, err.opener, err.err))
+            });
+        }
+        checker.finish(|err| streamed.push((err.offset, err.opener, err.err)));
+        assert_eq!(
+            streamed,
+            [
+                (
+                    3,
+                    Some(2),
+                    ParseBracketsErr::IncorrectClose {
+                        expected: quote,
+                        actual: guillemets
+                    }
+                ),
+                (5, Some(0), ParseBracketsErr::Unclosed(vec![guillemets]))
+            ]
+        );
+
+        // A snapshot taken part way through a char finishes it on restore
+        let mut checker = BracketChecker::with_alphabet(QUOTES.parse::<BracketSet>().unwrap());
+        let mut errors = vec![];
+        checker.feed(&[0xc2], |err| errors.push(err));
+        let snapshot = checker.snapshot();
+        checker.feed(b"x", |err| errors.push(err));
+        assert_eq!(errors.len(), 2);
+        errors.clear();
+        checker.restore(&snapshot);
+        checker.feed(&[0xab], |err| errors.push(err));
+        assert_eq!(errors, []);
+        assert_eq!(checker.depth(), 1);
+    }
+
+    #[test]
+    fn completes_lines() {
+        let completions: Vec<_> = include_str!("test.txt")
+            .lines()
+            .filter_map(|line| parse_brackets(line).ok())
+            .collect();
+        assert_eq!(completions[0].to_string(), "}}]])})]");
+        let scores = completions.iter().map(|c| c.score().unwrap());
+        assert_eq!(
+            scores.clone().collect::<Vec<_>>(),
+            [288957, 5566, 1480781, 995444, 294]
+        );
+        assert_eq!(middle_score(scores.clone()), Ok(288957));
+        assert_eq!(middle_score(scores.skip(1)), Err(EvenCountErr(4)));
+        assert_eq!(middle_score(Vec::<u128>::new()), Err(EvenCountErr(0)));
+
+        // 5^55 fits in a u128, 5^56 doesn't
+        let deep = "<".repeat(55);
+        assert!(parse_brackets(&deep).unwrap().score().is_some());
+        assert_eq!(parse_brackets(&(deep + "<")).unwrap().score(), None);
+        assert_eq!(parse_brackets("[]"), Ok(Completion::default()));
+    }
+
+    #[test]
+    fn fixes_make_valid_code() {
+        let lines = include_str!("input.txt").lines();
+        for recovery in [Recovery::Replace, Recovery::Delete, Recovery::CloseToMatch] {
+            for line in lines.clone() {
+                let errors = lint(line, recovery);
+                assert_eq!(
+                    parse_brackets(&apply_fixes(line, &errors)),
+                    Ok(Completion::default())
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use super::nesting::Nesting;
+use super::{Alphabet, BracketShape, Delimiter, ParseBracketsErr, Standard};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Recovery {
+    // Treat a wrong close as a typo for the expected one
+    Replace,
+    // Drop a wrong close as if it was never typed
+    Delete,
+    // Close everything opened since the matching open, or replace the close
+    // when nothing matches it
+    CloseToMatch,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Fix {
+    Replace { column: usize, with: char },
+    Insert { column: usize, text: String },
+    Delete { column: usize },
+}
+
+impl Fix {
+    pub const fn column(&self) -> usize {
+        match self {
+            Self::Replace { column, .. }
+            | Self::Insert { column, .. }
+            | Self::Delete { column } => *column,
+        }
+    }
+}
+
+impl Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Replace { column, with } => {
+                write!(f, "replace column {} with {}", column + 1, with)
+            }
+            Self::Insert { column, text } => {
+                write!(f, "insert {:?} at column {}", text, column + 1)
+            }
+            Self::Delete { column } => write!(f, "delete column {}", column + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct LintError<S = BracketShape> {
+    pub column: usize,
+    pub opener: Option<usize>,
+    pub err: ParseBracketsErr<S>,
+    pub fix: Fix,
+}
+
+impl<S: Delimiter> Display for LintError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "column {}: {}", self.column + 1, self.err)?;
+        if let Some(opener) = self.opener {
+            write!(f, " (opened at column {})", opener + 1)?;
+        }
+        write!(f, "; {}", self.fix)
+    }
+}
+
+impl<S: Delimiter + std::fmt::Debug + 'static> std::error::Error for LintError<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+fn closing<S: Delimiter>(open: &[(S, usize)]) -> String {
+    open.iter().rev().map(|(s, _)| s.close_char()).collect()
+}
+
+pub fn lint(text: &str, recovery: Recovery) -> Vec<LintError> {
+    lint_with(&Standard, text, recovery)
+}
+
+// Columns count chars from 0, the same as the fixes that refer to them
+pub fn lint_with<A: Alphabet>(
+    alphabet: &A,
+    text: &str,
+    recovery: Recovery,
+) -> Vec<LintError<A::Delim>> {
+    let mut nesting = Nesting::new();
+    let mut errors = vec![];
+    let mut len = 0;
+    for (column, c) in text.chars().enumerate() {
+        len = column + 1;
+        let err = match nesting.push(alphabet, c, column) {
+            Ok(()) => continue,
+            Err(err) => err,
+        };
+        let (opener, fix) = match err {
+            ParseBracketsErr::IncorrectClose { expected, actual } => {
+                let opener = nesting.innermost().map(|(_, opener)| opener);
+                let matching = nesting.open().iter().rposition(|&(s, _)| s == actual);
+                let fix = match (recovery, matching) {
+                    (Recovery::Delete, _) => Fix::Delete { column },
+                    (Recovery::CloseToMatch, Some(i)) => {
+                        let text = closing(&nesting.open()[i + 1..]);
+                        nesting.truncate(i);
+                        Fix::Insert { column, text }
+                    }
+                    _ => {
+                        nesting.pop();
+                        Fix::Replace {
+                            column,
+                            with: expected.close_char(),
+                        }
+                    }
+                };
+                (opener, fix)
+            }
+            _ => (None, Fix::Delete { column }),
+        };
+        errors.push(LintError {
+            column,
+            opener,
+            err,
+            fix,
+        });
+    }
+
+    if let Some((_, opener)) = nesting.outermost() {
+        errors.push(LintError {
+            column: len,
+            opener: Some(opener),
+            err: ParseBracketsErr::Unclosed(nesting.delims().collect()),
+            fix: Fix::Insert {
+                column: len,
+                text: closing(nesting.open()),
+            },
+        });
+    }
+    errors
+}
+
+pub fn apply_fixes<S>(text: &str, errors: &[LintError<S>]) -> String {
+    let mut chars: Vec<char> = text.chars().collect();
+    let mut fixes: Vec<&Fix> = errors.iter().map(|e| &e.fix).collect();
+    // Work backwards so earlier columns stay put
+    fixes.sort_by_key(|f| std::cmp::Reverse(f.column()));
+    for fix in fixes {
+        match fix {
+            Fix::Replace { column, with } => chars[*column] = *with,
+            Fix::Insert { column, text } => {
+                chars.splice(*column..*column, text.chars());
+            }
+            Fix::Delete { column } => {
+                chars.remove(*column);
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
@@ -0,0 +1,100 @@
+use super::{Bracket, BracketShape, BracketSide, Delimiter, ParseBracketsErr};
+
+// Decides which delimiter a char belongs to and which side of it the char
+// is. The innermost open delimiter is given so that a symmetric pair can tell
+// its close from a nested open.
+pub trait Alphabet {
+    type Delim: Delimiter;
+
+    fn classify(
+        &self,
+        c: char,
+        innermost: Option<Self::Delim>,
+    ) -> Option<(Self::Delim, BracketSide)>;
+}
+
+// The four bracket shapes
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct Standard;
+
+impl Alphabet for Standard {
+    type Delim = BracketShape;
+
+    fn classify(&self, c: char, _: Option<BracketShape>) -> Option<(BracketShape, BracketSide)> {
+        Bracket::try_from_char(c).map(|Bracket(shape, side)| (shape, side))
+    }
+}
+
+// The delimiters currently open, each with the position it was opened at
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Nesting<D, P> {
+    open: Vec<(D, P)>,
+}
+
+impl<D: Delimiter, P: Copy> Nesting<D, P> {
+    pub const fn new() -> Self {
+        Nesting { open: vec![] }
+    }
+
+    pub fn open(&self) -> &[(D, P)] {
+        &self.open
+    }
+
+    pub fn delims(&self) -> impl Iterator<Item = D> + '_ {
+        self.open.iter().map(|&(delim, _)| delim)
+    }
+
+    pub fn innermost(&self) -> Option<(D, P)> {
+        self.open.last().copied()
+    }
+
+    pub fn outermost(&self) -> Option<(D, P)> {
+        self.open.first().copied()
+    }
+
+    pub fn pop(&mut self) -> Option<(D, P)> {
+        self.open.pop()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.open.truncate(len);
+    }
+
+    // A wrong close leaves everything open, so the caller can decide how to
+    // recover from it
+    pub fn push<A: Alphabet<Delim = D>>(
+        &mut self,
+        alphabet: &A,
+        c: char,
+        pos: P,
+    ) -> Result<(), ParseBracketsErr<D>> {
+        let innermost = self.innermost().map(|(delim, _)| delim);
+        match alphabet.classify(c, innermost) {
+            None => Err(ParseBracketsErr::InvalidBracket(c)),
+            Some((delim, BracketSide::Open)) => {
+                self.open.push((delim, pos));
+                Ok(())
+            }
+            Some((actual, BracketSide::Close)) => match innermost {
+                Some(expected) if expected == actual => {
+                    self.open.pop();
+                    Ok(())
+                }
+                Some(expected) => Err(ParseBracketsErr::IncorrectClose { expected, actual }),
+                None => Err(ParseBracketsErr::UnexpectedClose(actual)),
+            },
+        }
+    }
+}
+
+// Stops at the first error, and otherwise gives whatever is left open
+pub fn parse_with<A: Alphabet>(
+    alphabet: &A,
+    text: &str,
+) -> Result<Vec<A::Delim>, ParseBracketsErr<A::Delim>> {
+    let mut nesting = Nesting::new();
+    for c in text.chars() {
+        nesting.push(alphabet, c, ())?;
+    }
+    Ok(nesting.delims().collect())
+}
@@ -0,0 +1,207 @@
+use std::fmt::Display;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use super::{get_autocomplete_value, get_syntax_error_value, BracketShape, Delimiter};
+use super::{parse_with, Alphabet, BracketSide, Completion, ParseBracketsErr};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Pair {
+    open: char,
+    close: char,
+}
+
+impl Pair {
+    pub const fn new(open: char, close: char) -> Self {
+        Pair { open, close }
+    }
+
+    pub const fn is_symmetric(&self) -> bool {
+        self.open == self.close
+    }
+}
+
+impl Delimiter for Pair {
+    fn open_char(&self) -> char {
+        self.open
+    }
+
+    fn close_char(&self) -> char {
+        self.close
+    }
+}
+
+impl From<BracketShape> for Pair {
+    fn from(shape: BracketShape) -> Self {
+        Pair::new(shape.open_char(), shape.close_char())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct PairScores {
+    pub syntax_error: u64,
+    pub autocomplete: u64,
+}
+
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct BracketSet {
+    pairs: Vec<(Pair, PairScores)>,
+}
+
+impl BracketSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn standard() -> Self {
+        BracketShape::values()
+            .into_iter()
+            .try_fold(Self::new(), |set, shape| {
+                set.with_pair(
+                    shape.into(),
+                    PairScores {
+                        syntax_error: get_syntax_error_value(shape),
+                        autocomplete: get_autocomplete_value(shape),
+                    },
+                )
+            })
+            .unwrap()
+    }
+
+    pub fn with_pair(mut self, pair: Pair, scores: PairScores) -> Result<Self, BracketSetErr> {
+        for c in [pair.open, pair.close] {
+            if self.lookup(c).is_some() {
+                return Err(BracketSetErr::DuplicateChar(c));
+            }
+        }
+        self.pairs.push((pair, scores));
+        Ok(self)
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = Pair> + '_ {
+        self.pairs.iter().map(|&(pair, _)| pair)
+    }
+
+    pub fn lookup(&self, c: char) -> Option<Pair> {
+        self.pairs
+            .iter()
+            .map(|&(pair, _)| pair)
+            .find(|pair| pair.open == c || pair.close == c)
+    }
+
+    pub fn scores(&self, pair: Pair) -> Option<PairScores> {
+        self.pairs
+            .iter()
+            .find(|&&(p, _)| p == pair)
+            .map(|&(_, scores)| scores)
+    }
+
+    // Code that's only missing closes is fine, the same as with the standard
+    // brackets
+    pub fn parse(&self, text: &str) -> Result<Completion<Pair>, ParseBracketsErr<Pair>> {
+        let unclosed = parse_with(self, text)?;
+        Ok(Completion { unclosed })
+    }
+
+    // None if the score doesn't fit, or the completion uses pairs from
+    // another set
+    pub fn autocomplete_score(&self, completion: &Completion<Pair>) -> Option<u128> {
+        completion.score_with(|pair| Some(self.scores(pair)?.autocomplete))
+    }
+}
+
+impl Alphabet for BracketSet {
+    type Delim = Pair;
+
+    // A symmetric delimiter closes itself when it's innermost, and opens a
+    // new level otherwise
+    fn classify(&self, c: char, innermost: Option<Pair>) -> Option<(Pair, BracketSide)> {
+        let pair = self.lookup(c)?;
+        let side = if c != pair.close || (pair.is_symmetric() && innermost != Some(pair)) {
+            BracketSide::Open
+        } else {
+            BracketSide::Close
+        };
+        Some((pair, side))
+    }
+}
+
+// One pair per line: the open and close chars, the syntax error score and
+// the autocomplete score. Blank lines and lines starting with # are skipped.
+impl FromStr for BracketSet {
+    type Err = BracketSetErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = Self::new();
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 4 {
+                return Err(BracketSetErr::WrongFieldCount {
+                    line: line_no,
+                    count: fields.len(),
+                });
+            }
+            let parse_char = |field: &str| {
+                let mut chars = field.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(BracketSetErr::NotOneChar {
+                        line: line_no,
+                        field: field.to_string(),
+                    }),
+                }
+            };
+            let parse_score = |field: &str| {
+                field
+                    .parse()
+                    .map_err(|err| BracketSetErr::InvalidScore { line: line_no, err })
+            };
+            let pair = Pair::new(parse_char(fields[0])?, parse_char(fields[1])?);
+            let scores = PairScores {
+                syntax_error: parse_score(fields[2])?,
+                autocomplete: parse_score(fields[3])?,
+            };
+            set = set.with_pair(pair, scores)?;
+        }
+        Ok(set)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BracketSetErr {
+    DuplicateChar(char),
+    WrongFieldCount { line: usize, count: usize },
+    NotOneChar { line: usize, field: String },
+    InvalidScore { line: usize, err: ParseIntError },
+}
+
+impl Display for BracketSetErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateChar(c) => write!(f, "{:?} is used by more than one pair", c),
+            Self::WrongFieldCount { line, count } => write!(
+                f,
+                "line {}: expected open, close and two scores, found {} fields",
+                line, count
+            ),
+            Self::NotOneChar { line, field } => {
+                write!(f, "line {}: {:?} is not a single char", line, field)
+            }
+            Self::InvalidScore { line, err } => write!(f, "line {}: invalid score: {}", line, err),
+        }
+    }
+}
+
+impl std::error::Error for BracketSetErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidScore { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
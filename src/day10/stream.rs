@@ -0,0 +1,223 @@
+use std::fmt::Display;
+use std::io::{self, Read};
+
+use super::nesting::Nesting;
+use super::{Alphabet, BracketShape, Delimiter, ParseBracketsErr, Standard};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct BracketError<S = BracketShape> {
+    pub offset: u64,
+    pub opener: Option<u64>,
+    pub err: ParseBracketsErr<S>,
+}
+
+impl<S: Delimiter> Display for BracketError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.err)?;
+        if let Some(opener) = self.opener {
+            write!(f, " (opened at byte {})", opener)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Delimiter + std::fmt::Debug + 'static> std::error::Error for BracketError<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Snapshot<S = BracketShape> {
+    nesting: Nesting<S, u64>,
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl<S: Delimiter> Snapshot<S> {
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn depth(&self) -> usize {
+        self.nesting.open().len()
+    }
+}
+
+// Checks brackets a chunk at a time, carrying on after errors by treating a
+// wrong close as the expected one and skipping anything else that's invalid
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct BracketChecker<A: Alphabet = Standard> {
+    alphabet: A,
+    nesting: Nesting<A::Delim, u64>,
+    offset: u64,
+    per_line: bool,
+    // The start of a char whose bytes haven't all arrived yet
+    partial: Vec<u8>,
+}
+
+impl BracketChecker {
+    pub fn new() -> Self {
+        Self::with_alphabet(Standard)
+    }
+
+    // Each line is checked on its own, and ends like the end of the input
+    pub fn per_line() -> Self {
+        Self::new().split_lines()
+    }
+}
+
+impl Default for BracketChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+impl<A: Alphabet> BracketChecker<A> {
+    pub fn with_alphabet(alphabet: A) -> Self {
+        BracketChecker {
+            alphabet,
+            nesting: Nesting::new(),
+            offset: 0,
+            per_line: false,
+            partial: vec![],
+        }
+    }
+
+    pub fn split_lines(self) -> Self {
+        BracketChecker {
+            per_line: true,
+            ..self
+        }
+    }
+
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn depth(&self) -> usize {
+        self.nesting.open().len()
+    }
+
+    pub fn snapshot(&self) -> Snapshot<A::Delim> {
+        Snapshot {
+            nesting: self.nesting.clone(),
+            offset: self.offset,
+            partial: self.partial.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot<A::Delim>) {
+        self.nesting.clone_from(&snapshot.nesting);
+        self.offset = snapshot.offset;
+        self.partial.clone_from(&snapshot.partial);
+    }
+
+    pub fn feed(&mut self, chunk: &[u8], mut on_error: impl FnMut(BracketError<A::Delim>)) {
+        for &byte in chunk {
+            self.feed_byte(byte, &mut on_error);
+            self.offset += 1;
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8, on_error: &mut impl FnMut(BracketError<A::Delim>)) {
+        if let Some(&lead) = self.partial.first() {
+            if (0x80..0xc0).contains(&byte) {
+                self.partial.push(byte);
+                if self.partial.len() == utf8_len(lead) {
+                    let c = std::str::from_utf8(&self.partial)
+                        .map_or(char::REPLACEMENT_CHARACTER, |s| s.chars().next().unwrap());
+                    let start = self.offset + 1 - self.partial.len() as u64;
+                    self.partial.clear();
+                    self.feed_char(c, start, on_error);
+                }
+                return;
+            }
+            self.flush_partial(on_error);
+        }
+
+        match byte {
+            b'\n' if self.per_line => self.end(on_error),
+            b'\r' if self.per_line => {}
+            _ if byte.is_ascii() => self.feed_char(byte as char, self.offset, on_error),
+            _ if utf8_len(byte) > 1 => self.partial.push(byte),
+            _ => self.feed_char(char::REPLACEMENT_CHARACTER, self.offset, on_error),
+        }
+    }
+
+    // A char cut short is reported once, at its first byte
+    fn flush_partial(&mut self, on_error: &mut impl FnMut(BracketError<A::Delim>)) {
+        if !self.partial.is_empty() {
+            let start = self.offset - self.partial.len() as u64;
+            self.partial.clear();
+            self.feed_char(char::REPLACEMENT_CHARACTER, start, on_error);
+        }
+    }
+
+    fn feed_char(
+        &mut self,
+        c: char,
+        offset: u64,
+        on_error: &mut impl FnMut(BracketError<A::Delim>),
+    ) {
+        let err = match self.nesting.push(&self.alphabet, c, offset) {
+            Ok(()) => return,
+            Err(err) => err,
+        };
+        let opener = match err {
+            ParseBracketsErr::IncorrectClose { .. } => self.nesting.pop().map(|(_, opener)| opener),
+            _ => None,
+        };
+        on_error(BracketError {
+            offset,
+            opener,
+            err,
+        });
+    }
+
+    fn end(&mut self, on_error: &mut impl FnMut(BracketError<A::Delim>)) {
+        if let Some((_, opener)) = self.nesting.outermost() {
+            on_error(BracketError {
+                offset: self.offset,
+                opener: Some(opener),
+                err: ParseBracketsErr::Unclosed(self.nesting.delims().collect()),
+            });
+            self.nesting.truncate(0);
+        }
+    }
+
+    // Reports anything left open, ready to check another input from scratch
+    pub fn finish(&mut self, mut on_error: impl FnMut(BracketError<A::Delim>)) {
+        self.flush_partial(&mut on_error);
+        self.end(&mut on_error);
+        self.offset = 0;
+    }
+
+    pub fn check_reader(
+        &mut self,
+        mut reader: impl Read,
+        mut on_error: impl FnMut(BracketError<A::Delim>),
+    ) -> io::Result<()> {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            self.feed(&buf[..len], &mut on_error);
+        }
+        self.finish(on_error);
+        Ok(())
+    }
+}
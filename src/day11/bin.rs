@@ -1,98 +1,547 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 
-use collect_array::CollectArrayResult;
+use arrayvec::ArrayVec;
 
-const WIDTH: usize = 10;
-const HEIGHT: usize = 10;
+mod cycle;
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Neighbourhood {
+    // All 8 surrounding octopi
+    Moore,
+    // Only the 4 octopi sharing an edge
+    VonNeumann,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Rules {
+    pub threshold: u8,
+    pub reset: u8,
+    pub flash_gain: u8,
+    pub neighbourhood: Neighbourhood,
+    pub max_flashes: u8,
+}
+
+impl Rules {
+    pub const OCTOPI: Self = Rules {
+        threshold: 9,
+        reset: 0,
+        flash_gain: 1,
+        neighbourhood: Neighbourhood::Moore,
+        max_flashes: 1,
+    };
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::OCTOPI
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct StepStats {
+    // In the order they flashed, with octopi that flash more than once
+    // appearing each time
+    pub flashes: Vec<(usize, usize)>,
+    pub num_flashed: usize,
+    pub synchronized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Octopi {
-    energy: [[u8; WIDTH]; HEIGHT],
+    energy: Vec<u8>,
+    width: NonZeroUsize,
+    wrapping: bool,
+    rules: Rules,
 }
 
 impl Octopi {
-    pub fn new(energy: [[u8; WIDTH]; HEIGHT]) -> Self {
-        Octopi { energy }
+    pub fn new(energy: Vec<u8>, width: NonZeroUsize) -> Self {
+        assert!(
+            energy.len() % width == 0,
+            "energy doesn't fill {} wide rows",
+            width
+        );
+        Octopi {
+            energy,
+            width,
+            wrapping: false,
+            rules: Rules::OCTOPI,
+        }
+    }
+
+    pub fn with_rules(self, rules: Rules) -> Self {
+        assert!(rules.max_flashes > 0, "octopi must be able to flash");
+        Octopi { rules, ..self }
+    }
+
+    pub const fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    // Octopi on an edge neighbour the ones on the opposite edge
+    pub fn with_wrapping(self, wrapping: bool) -> Self {
+        Octopi { wrapping, ..self }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width.get(), self.height())
+    }
+
+    pub fn len(&self) -> usize {
+        self.energy.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.energy.is_empty()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.energy[self.get_pos(x, y)]
+    }
+
+    // Every octopus is back to its reset energy, as after they all flash
+    pub fn is_synchronized(&self) -> bool {
+        self.energy.iter().all(|&e| e == self.rules.reset)
     }
 
     pub fn increment_all(&mut self) -> u32 {
+        self.step().flashes.len() as u32
+    }
+
+    pub fn step(&mut self) -> StepStats {
+        let mut flash_counts = vec![0; self.len()];
+        let mut queue = VecDeque::new();
+        let mut flashes = Vec::new();
+        for pos in 0..self.len() {
+            self.energy[pos] = self.energy[pos].saturating_add(1);
+            self.try_flash(pos, &mut flash_counts, &mut queue);
+        }
+        while let Some(pos) = queue.pop_front() {
+            let (x, y) = (pos % self.width, pos / self.width);
+            flashes.push((x, y));
+            for (adj_x, adj_y) in self.neighbours(x, y) {
+                let adj = self.get_pos(adj_x, adj_y);
+                // Octopi that can't flash again this step stay reset
+                if flash_counts[adj] < self.rules.max_flashes {
+                    self.energy[adj] = self.energy[adj].saturating_add(self.rules.flash_gain);
+                    self.try_flash(adj, &mut flash_counts, &mut queue);
+                }
+            }
+        }
+        let num_flashed = flash_counts.iter().filter(|&&n| n > 0).count();
+        StepStats {
+            flashes,
+            num_flashed,
+            synchronized: num_flashed == self.len(),
+        }
+    }
+
+    fn try_flash(&mut self, pos: usize, flash_counts: &mut [u8], queue: &mut VecDeque<usize>) {
+        if self.energy[pos] > self.rules.threshold && flash_counts[pos] < self.rules.max_flashes {
+            flash_counts[pos] += 1;
+            self.energy[pos] = self.rules.reset;
+            queue.push_back(pos);
+        }
+    }
+
+    pub fn neighbours(&self, x: usize, y: usize) -> ArrayVec<(usize, usize), 8> {
+        let (width, height) = self.size();
+        let mut adj = ArrayVec::new();
+        let step = |i: usize, d: isize, len: usize| {
+            if self.wrapping {
+                Some((i as isize + d).rem_euclid(len as isize) as usize)
+            } else {
+                i.checked_add_signed(d).filter(|&i| i < len)
+            }
+        };
+        for dy in [-1, 0, 1] {
+            for dx in [-1, 0, 1] {
+                if self.rules.neighbourhood == Neighbourhood::VonNeumann && dx != 0 && dy != 0 {
+                    continue;
+                }
+                let (Some(adj_x), Some(adj_y)) = (step(x, dx, width), step(y, dy, height)) else {
+                    continue;
+                };
+                // Small wrapping grids reach the same octopus more than once
+                if (adj_x, adj_y) != (x, y) && !adj.contains(&(adj_x, adj_y)) {
+                    adj.push((adj_x, adj_y));
+                }
+            }
+        }
+        adj
+    }
+
+    fn height(&self) -> usize {
+        self.energy.len() / self.width
+    }
+
+    fn get_pos(&self, x: usize, y: usize) -> usize {
+        y * self.width.get() + x
+    }
+
+    fn print(&self) {
+        print!("{}", self);
+    }
+}
+
+impl Display for Octopi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.energy.chunks_exact(self.width.get()) {
+            for e in row {
+                write!(f, "{}", e)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OctopiParseErr {
+    InvalidDigit {
+        byte: u8,
+    },
+    EmptyFirstLine,
+    InconsistentWidths {
+        line: usize,
+        expected: NonZeroUsize,
+        actual: usize,
+    },
+}
+
+impl Display for OctopiParseErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidDigit { byte } => write!(f, "invalid energy level {:?}", *byte as char),
+            Self::EmptyFirstLine => write!(f, "first line was empty"),
+            Self::InconsistentWidths {
+                line,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "expected all rows to be {} wide, but line {} was {} wide",
+                expected, line, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OctopiParseErr {}
+
+impl FromStr for Octopi {
+    type Err = OctopiParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let width: NonZeroUsize = s
+            .lines()
+            .next()
+            .unwrap_or("")
+            .len()
+            .try_into()
+            .or(Err(Self::Err::EmptyFirstLine))?;
+
+        let mut energy = vec![];
+        for (i, line) in s.lines().enumerate() {
+            if line.len() != width.get() {
+                return Err(Self::Err::InconsistentWidths {
+                    line: i + 1,
+                    expected: width,
+                    actual: line.len(),
+                });
+            }
+            for byte in line.bytes() {
+                if let b'0'..=b'9' = byte {
+                    energy.push(byte - b'0');
+                } else {
+                    return Err(Self::Err::InvalidDigit { byte });
+                }
+            }
+        }
+        Ok(Octopi::new(energy, width))
+    }
+}
+
+pub fn main() {
+    let octopi: Octopi = include_str!("input.txt").parse().unwrap();
+
+    octopi.print();
+
+    let cycle = octopi.find_cycle(1_000_000).unwrap();
+    println!(
+        "P1: {} flashes after 100 increments",
+        cycle.flashes_after(100)
+    );
+    println!(
+        "P1: {} steps to all flash",
+        cycle.first_synchronized().unwrap()
+    );
+    println!(
+        "Repeats every {} steps from step {}: {} flashes after 10^18 steps",
+        cycle.period(),
+        cycle.start(),
+        cycle.flashes_after(10u64.pow(18))
+    );
+
+    benchmark(include_str!("input.txt").parse().unwrap(), 1000);
+    let tiled: String = include_str!("input.txt")
+        .lines()
+        .map(|line| line.repeat(10) + "\n")
+        .collect::<String>()
+        .repeat(10);
+    benchmark(tiled.parse().unwrap(), 100);
+
+    let mut torus: Octopi = include_str!("input.txt").parse().unwrap();
+    torus = torus.with_wrapping(true);
+    let (width, height) = torus.size();
+    let torus_flashes: u32 = (0..100).map(|_| torus.increment_all()).sum();
+    println!(
+        "Wrapping {}x{} grid of {}: {} flashes after 100 increments",
+        width,
+        height,
+        torus.len(),
+        torus_flashes
+    );
+    match torus.find_cycle(1_000_000) {
+        Some(cycle) => match cycle.first_synchronized() {
+            Some(step) => println!("  synchronizes after {} more steps", step),
+            None => println!(
+                "  never synchronizes, repeating every {} steps",
+                cycle.period()
+            ),
+        },
+        None => println!("  no repeat within 1000000 steps"),
+    }
+
+    let mut variant: Octopi = include_str!("input.txt").parse().unwrap();
+    variant = variant.with_rules(Rules {
+        flash_gain: 3,
+        neighbourhood: Neighbourhood::VonNeumann,
+        max_flashes: 2,
+        ..Rules::OCTOPI
+    });
+    println!("Variant rules: {:?}", variant.rules());
+    for step in 1..=5 {
+        let stats = variant.step();
+        println!(
+            "  step {}: {} flashes from {} octopi{}",
+            step,
+            stats.flashes.len(),
+            stats.num_flashed,
+            if stats.synchronized {
+                ", synchronized"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+fn benchmark(mut octopi: Octopi, steps: usize) {
+    use std::time::Instant;
+
+    let before_time = Instant::now();
+    let flashes: usize = (0..steps).map(|_| octopi.step().flashes.len()).sum();
+    let (width, height) = octopi.size();
+    println!(
+        "Runtime for {} steps of {}x{}: {:.3}ms ({} flashes)",
+        steps,
+        width,
+        height,
+        before_time.elapsed().as_secs_f64() * 1000.0,
+        flashes
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // The original engine, which only knows the standard rules, kept to check
+    // the queued one against
+    fn increment_all_recursive(octopi: &mut Octopi) -> u32 {
+        let (width, height) = octopi.size();
         let mut to_reset: HashSet<(usize, usize)> = HashSet::new();
         let mut flashes = 0;
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                flashes += self.increment(x, y, &mut to_reset);
+        for y in 0..height {
+            for x in 0..width {
+                flashes += increment(octopi, x, y, &mut to_reset);
             }
         }
         for (x, y) in to_reset {
-            self.energy[y][x] = 0;
+            let pos = octopi.get_pos(x, y);
+            octopi.energy[pos] = 0;
         }
         flashes
     }
 
-    fn increment(&mut self, x: usize, y: usize, mut to_reset: &mut HashSet<(usize, usize)>) -> u32 {
+    fn increment(
+        octopi: &mut Octopi,
+        x: usize,
+        y: usize,
+        to_reset: &mut HashSet<(usize, usize)>,
+    ) -> u32 {
         let mut flashes = 0;
-        if self.energy[y][x] == 9 {
+        let pos = octopi.get_pos(x, y);
+        if octopi.energy[pos] == 9 {
             if to_reset.insert((x, y)) {
                 flashes += 1;
 
-                let min_x = x.saturating_sub(1);
-                let max_x = x.saturating_add(1).min(WIDTH - 1);
-                let min_y = y.saturating_sub(1);
-                let max_y = y.saturating_add(1).min(HEIGHT - 1);
-                for adj_y in min_y..=max_y {
-                    for adj_x in min_x..=max_x {
-                        if adj_x != x || adj_y != y {
-                            flashes += self.increment(adj_x, adj_y, &mut to_reset);
-                        }
-                    }
+                for (adj_x, adj_y) in octopi.neighbours(x, y) {
+                    flashes += increment(octopi, adj_x, adj_y, to_reset);
                 }
             }
         } else {
-            self.energy[y][x] += 1;
+            octopi.energy[pos] += 1;
         }
         flashes
     }
 
-    fn print(&self) {
-        for row in self.energy {
-            for e in row {
-                print!("{}", e);
+    #[test]
+    fn steps_any_size() {
+        let mut octopi: Octopi = include_str!("test.txt").parse().unwrap();
+        let flashes: u32 = (0..100).map(|_| octopi.increment_all()).sum();
+        assert_eq!(flashes, 1656);
+        let first_sync = (101..).find(|_| {
+            octopi.increment_all();
+            octopi.is_synchronized()
+        });
+        assert_eq!(first_sync, Some(195));
+
+        let mut octopi: Octopi = "11111\n19991\n19191\n19991\n11111".parse().unwrap();
+        assert_eq!(octopi.size(), (5, 5));
+        assert_eq!(octopi.increment_all(), 9);
+        assert_eq!(octopi.to_string(), "34543\n40004\n50005\n40004\n34543\n");
+
+        assert_eq!(
+            "123\n45".parse::<Octopi>(),
+            Err(OctopiParseErr::InconsistentWidths {
+                line: 2,
+                expected: NonZeroUsize::new(3).unwrap(),
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn step_matches_recursive() {
+        let octopi: Octopi = include_str!("test.txt").parse().unwrap();
+        for wrapping in [false, true] {
+            let mut queued = octopi.clone().with_wrapping(wrapping);
+            let mut recursive = queued.clone();
+            for _ in 0..200 {
+                let flashes = queued.step().flashes;
+                assert_eq!(
+                    flashes.len() as u32,
+                    increment_all_recursive(&mut recursive)
+                );
+                assert_eq!(queued, recursive);
+                assert!(flashes.iter().all(|&(x, y)| queued.get(x, y) == 0));
             }
-            println!();
         }
-    }
-}
 
-pub fn main() {
-    let input: CollectArrayResult<_, HEIGHT> = include_str!("input.txt")
-        .lines()
-        .map(|r| {
-            let r: &[u8; WIDTH] = r.as_bytes().try_into().unwrap();
-            r.map(|b| b - b'0')
-        })
-        .collect();
-    let mut octopi = Octopi::new(input.unwrap());
+        let mut octopi: Octopi = "11111\n19991\n19191\n19991\n11111".parse().unwrap();
+        let flashes = octopi.step().flashes;
+        assert_eq!(flashes[..4], [(1, 1), (2, 1), (3, 1), (1, 2)]);
+        assert_eq!(flashes.last(), Some(&(2, 2)));
+    }
 
-    octopi.print();
+    #[test]
+    fn finds_cycles() {
+        let octopi: Octopi = include_str!("test.txt").parse().unwrap();
+        let cycle = octopi.find_cycle(1000).unwrap();
+        assert_eq!(cycle.first_synchronized(), Some(195));
+        assert_eq!(cycle.flashes_after(10), 204);
+        assert_eq!(cycle.flashes_after(100), 1656);
 
-    let mut flashes = 0;
-    let mut first_all_flash = None;
-    for i in 1..=100 {
-        let curr_flashes = octopi.increment_all();
-        if curr_flashes == 100 {
-            first_all_flash.get_or_insert(i);
+        let mut stepped = octopi.clone();
+        let mut flashes = 0;
+        for step in 1..=(cycle.start() + 3 * cycle.period() + 4) {
+            flashes += stepped.increment_all() as u128;
+            assert_eq!(cycle.flashes_after(step), flashes);
         }
-        flashes += curr_flashes;
+        let end = cycle.start() + cycle.period();
+        assert_eq!(octopi.find_cycle(end - 1), None);
+        assert_eq!(octopi.find_cycle(end), Some(cycle));
+
+        // The two octopi take turns to flash, so never flash together
+        let octopi: Octopi = "05".parse().unwrap();
+        let cycle = octopi.find_cycle(100).unwrap();
+        assert_eq!((cycle.start(), cycle.period()), (0, 9));
+        assert_eq!(cycle.first_synchronized(), None);
+        assert_eq!(cycle.flashes_after(9 * 10u64.pow(17)), 2 * 10u128.pow(17));
     }
 
-    println!("P1: {} flashes after 100 increments", flashes);
+    #[test]
+    fn follows_rules() {
+        let octopi: Octopi = "000\n090\n000".parse().unwrap();
+        let mut moore = octopi.clone();
+        assert_eq!(moore.step().num_flashed, 1);
+        assert_eq!(moore.to_string(), "222\n202\n222\n");
+        let mut von_neumann = octopi.with_rules(Rules {
+            neighbourhood: Neighbourhood::VonNeumann,
+            ..Rules::OCTOPI
+        });
+        assert_eq!(von_neumann.step().num_flashed, 1);
+        assert_eq!(von_neumann.to_string(), "121\n202\n121\n");
 
-    let first_all_flash = first_all_flash.unwrap_or_else(|| {
-        for i in 101..=u64::MAX {
-            if octopi.increment_all() == 100 {
-                return i;
-            }
-        }
-        panic!()
-    });
-    println!("P1: {} steps to all flash", first_all_flash);
+        // Each flash charges the other octopus straight back up
+        let octopi: Octopi = "99".parse().unwrap();
+        let mut octopi = octopi.with_rules(Rules {
+            flash_gain: 10,
+            max_flashes: 2,
+            ..Rules::OCTOPI
+        });
+        let stats = octopi.step();
+        assert_eq!(stats.flashes, [(0, 0), (1, 0), (1, 0), (0, 0)]);
+        assert_eq!(stats.num_flashed, 2);
+        assert!(stats.synchronized);
+        assert_eq!(octopi.to_string(), "00\n");
+
+        // Octopi keep flashing at the threshold they're reset to
+        let octopi: Octopi = "5".parse().unwrap();
+        let mut octopi = octopi.with_rules(Rules {
+            threshold: 5,
+            reset: 5,
+            ..Rules::OCTOPI
+        });
+        assert!(octopi.is_synchronized());
+        let cycle = octopi.find_cycle(10).unwrap();
+        assert_eq!((cycle.start(), cycle.period()), (0, 1));
+        assert_eq!(cycle.first_synchronized(), Some(1));
+        assert_eq!(octopi.increment_all(), 1);
+    }
+
+    #[test]
+    fn wraps_edges() {
+        let octopi: Octopi = "000\n000\n000\n000".parse().unwrap();
+        assert_eq!(octopi.neighbours(0, 0).len(), 3);
+        let octopi = octopi.with_wrapping(true);
+        let mut adj = octopi.neighbours(0, 0).into_iter().collect::<Vec<_>>();
+        adj.sort();
+        assert_eq!(
+            adj,
+            [
+                (0, 1),
+                (0, 3),
+                (1, 0),
+                (1, 1),
+                (1, 3),
+                (2, 0),
+                (2, 1),
+                (2, 3)
+            ]
+        );
+
+        // Every octopus neighbours every other one on a 2x2 torus
+        let mut octopi: Octopi = "90\n00".parse().unwrap();
+        octopi = octopi.with_wrapping(true);
+        assert_eq!(octopi.neighbours(0, 0).len(), 3);
+        assert_eq!(octopi.increment_all(), 1);
+        assert_eq!(octopi.to_string(), "02\n22\n");
+    }
 }
@@ -0,0 +1,105 @@
+use super::{Octopi, StepStats};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Cycle {
+    start: u64,
+    period: u64,
+    // Total flashes after each step, up to the end of the first cycle
+    flashes: Vec<u64>,
+    first_synchronized: Option<u64>,
+}
+
+impl Cycle {
+    // The first step whose state comes round again
+    pub const fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub const fn period(&self) -> u64 {
+        self.period
+    }
+
+    pub fn flashes_after(&self, steps: u64) -> u128 {
+        let end = self.start + self.period;
+        if steps <= end {
+            return self.flashes[steps as usize] as u128;
+        }
+        let before = self.flashes[self.start as usize];
+        let per_cycle = (self.flashes[end as usize] - before) as u128;
+        let (cycles, rest) = (
+            (steps - self.start) / self.period,
+            (steps - self.start) % self.period,
+        );
+        let partial = self.flashes[(self.start + rest) as usize] - before;
+        before as u128 + cycles as u128 * per_cycle + partial as u128
+    }
+
+    // Every state after the start repeats within the first cycle, so if no
+    // step synchronized by then, none ever will
+    pub const fn first_synchronized(&self) -> Option<u64> {
+        self.first_synchronized
+    }
+}
+
+impl Octopi {
+    // Uses Brent's algorithm, so only a few grids are kept however long the
+    // octopi take to repeat
+    pub fn find_cycle(&self, max_steps: u64) -> Option<Cycle> {
+        // Find the period. The hare is at most three times as far as the end
+        // of the first cycle when it catches up, so any cycle ending within
+        // `max_steps` is found by then.
+        let mut tortoise = self.clone();
+        let mut hare = self.clone();
+        hare.step();
+        let (mut power, mut period, mut hare_steps) = (1, 1, 1);
+        while tortoise != hare {
+            if hare_steps >= max_steps.saturating_mul(3) {
+                return None;
+            }
+            if power == period {
+                tortoise.clone_from(&hare);
+                power *= 2;
+                period = 0;
+            }
+            hare.step();
+            period += 1;
+            hare_steps += 1;
+        }
+
+        // Find the start, with the hare a period ahead
+        let mut flashes = vec![0];
+        let mut first_synchronized = None;
+        let mut record = |stats: StepStats| {
+            flashes.push(flashes.last().unwrap() + stats.flashes.len() as u64);
+            if stats.synchronized {
+                first_synchronized.get_or_insert(flashes.len() as u64 - 1);
+            }
+        };
+        let mut tortoise = self.clone();
+        let mut hare = self.clone();
+        for _ in 0..period {
+            hare.step();
+        }
+        let mut start = 0;
+        while tortoise != hare {
+            if start + period >= max_steps {
+                return None;
+            }
+            record(tortoise.step());
+            hare.step();
+            start += 1;
+        }
+        if start + period > max_steps {
+            return None;
+        }
+        for _ in 0..period {
+            record(tortoise.step());
+        }
+        Some(Cycle {
+            start,
+            period,
+            flashes,
+            first_synchronized,
+        })
+    }
+}
@@ -1,5 +1,10 @@
 use std::collections::HashMap;
 
+use bitvec::prelude::*;
+
+mod paths;
+use paths::VisitPolicy;
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum Cave {
     // Start is not included, as it is not a valid destination
@@ -13,88 +18,109 @@ pub struct CaveSystem {
     big: Vec<Vec<Cave>>,
     small: Vec<Vec<Cave>>,
     start: Vec<Cave>,
+    big_names: Vec<String>,
+    small_names: Vec<String>,
 }
 
 impl CaveSystem {
-    pub fn count_paths(&self) -> (u32, u32) {
-        let mut num_paths_no_twice = 0;
-        let mut num_paths_with_twice = 0;
-
-        struct ToVisit {
-            index: usize,
-            is_small: bool,
-            small_visited: u64,
-            twice_unused: bool,
+    // The start is None
+    pub fn name(&self, cave: Option<Cave>) -> &str {
+        match cave {
+            None => "start",
+            Some(Cave::End) => "end",
+            Some(Cave::Big(index)) => &self.big_names[index],
+            Some(Cave::Small(index)) => &self.small_names[index],
         }
+    }
 
-        let mut to_visit = Vec::with_capacity(64);
-        for dest in &self.start {
-            let (index, is_small) = match *dest {
-                Cave::End => {
-                    num_paths_no_twice += 1;
-                    num_paths_with_twice += 1;
-                    continue;
-                }
-                Cave::Big(index) => (index, false),
-                Cave::Small(index) => (index, true),
-            };
-            to_visit.push(ToVisit {
-                index,
-                is_small,
-                small_visited: 0,
-                twice_unused: true,
-            });
+    pub fn find_cave(&self, name: &str) -> Option<Cave> {
+        if name == "end" {
+            return Some(Cave::End);
+        }
+        let find = |names: &[String]| names.iter().position(|n| n == name);
+        find(&self.big_names)
+            .map(Cave::Big)
+            .or_else(|| find(&self.small_names).map(Cave::Small))
+    }
+
+    pub fn connections(&self, cave: Option<Cave>) -> &[Cave] {
+        match cave {
+            None => &self.start,
+            Some(Cave::End) => &[],
+            Some(Cave::Big(index)) => &self.big[index],
+            Some(Cave::Small(index)) => &self.small[index],
         }
+    }
 
-        assert!(
-            self.small.len() < 64,
-            "64 or more small caves will break the 'visited' bitfield"
-        );
+    // Returns the number of paths visiting small caves once, and the number
+    // where one small cave can be visited twice, or None for a count too
+    // large for a u128
+    pub fn count_paths(&self) -> (Option<u128>, Option<u128>) {
+        let mut memo = HashMap::new();
+        let num_paths_no_twice = self.count_paths_from(true, &mut memo);
+        let num_paths_with_twice = self.count_paths_from(false, &mut memo);
+        (num_paths_no_twice, num_paths_with_twice)
+    }
 
-        while let Some(curr) = to_visit.pop() {
-            let mut small_visited = curr.small_visited;
-            let all_dest = if curr.is_small {
-                small_visited |= 1 << curr.index;
-                &self.small[curr.index]
-            } else {
-                &self.big[curr.index]
-            };
-            for dest in all_dest {
-                match *dest {
-                    Cave::End => {
-                        num_paths_with_twice += 1;
-                        if curr.twice_unused {
-                            num_paths_no_twice += 1;
+    // Searches with an explicit stack, as paths can be as long as the cave
+    // system is big
+    fn count_paths_from(
+        &self,
+        twice_used: bool,
+        memo: &mut HashMap<(Option<Cave>, BitVec, bool), u128>,
+    ) -> Option<u128> {
+        struct Frame {
+            key: (Option<Cave>, BitVec, bool),
+            next: usize,
+            num_paths: u128,
+        }
+
+        let mut stack = vec![Frame {
+            key: (None, bitvec![0; self.small.len()], twice_used),
+            next: 0,
+            num_paths: 0,
+        }];
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let (cave, visited, twice_used) = &frame.key;
+            let dest = match self.connections(*cave).get(frame.next) {
+                Some(&dest) => dest,
+                None => {
+                    let Frame { key, num_paths, .. } = stack.pop().unwrap();
+                    memo.insert(key, num_paths);
+                    match stack.last_mut() {
+                        Some(parent) => {
+                            parent.num_paths = parent.num_paths.checked_add(num_paths)?
                         }
+                        None => return Some(num_paths),
                     }
-                    Cave::Big(index) => {
-                        to_visit.push(ToVisit {
-                            index,
-                            is_small: false,
-                            small_visited,
-                            twice_unused: curr.twice_unused,
-                        });
-                    }
-                    Cave::Small(index) => {
-                        let first_visit = small_visited & (1 << index) == 0;
-                        let twice_unused = if first_visit {
-                            curr.twice_unused
-                        } else if curr.twice_unused {
-                            false
-                        } else {
-                            continue;
-                        };
-                        to_visit.push(ToVisit {
-                            index,
-                            is_small: true,
-                            small_visited,
-                            twice_unused,
-                        });
-                    }
+                    continue;
+                }
+            };
+            frame.next += 1;
+            let key = match dest {
+                Cave::End => {
+                    frame.num_paths = frame.num_paths.checked_add(1)?;
+                    continue;
                 }
+                Cave::Big(_) => (Some(dest), visited.clone(), *twice_used),
+                Cave::Small(index) if !visited[index] => {
+                    let mut visited = visited.clone();
+                    visited.set(index, true);
+                    (Some(dest), visited, *twice_used)
+                }
+                Cave::Small(_) if !twice_used => (Some(dest), visited.clone(), true),
+                Cave::Small(_) => continue,
+            };
+            match memo.get(&key) {
+                Some(&num_paths) => frame.num_paths = frame.num_paths.checked_add(num_paths)?,
+                None => stack.push(Frame {
+                    key,
+                    next: 0,
+                    num_paths: 0,
+                }),
             }
         }
-        (num_paths_no_twice, num_paths_with_twice)
     }
 }
 
@@ -129,10 +155,12 @@ impl<'a> CaveSystemBuilder<'a> {
                 if name.chars().next().unwrap().is_lowercase() {
                     let index = self.caves.small.len();
                     self.caves.small.push(vec![]);
+                    self.caves.small_names.push(name.to_string());
                     Cave::Small(index)
                 } else {
                     let index = self.caves.big.len();
                     self.caves.big.push(vec![]);
+                    self.caves.big_names.push(name.to_string());
                     Cave::Big(index)
                 }
             })),
@@ -175,6 +203,166 @@ pub fn main() {
         (done_time - before_build_time).as_secs_f64() * 1000.0,
     );
 
-    println!("P1: {} paths", num_paths_p1);
-    println!("P2: {} paths", num_paths_p2);
+    println!("P1: {} paths", num_paths_p1.unwrap());
+    println!("P2: {} paths", num_paths_p2.unwrap());
+
+    let ladder_input = ladder(100);
+    let before_ladder_time = Instant::now();
+    let ladder = build(&ladder_input);
+    let (num_ladder_p1, num_ladder_p2) = ladder.count_paths();
+    let show = |num_paths: Option<u128>| match num_paths {
+        Some(num_paths) => num_paths.to_string(),
+        None => String::from("too many"),
+    };
+    println!(
+        "Ladder of {} small caves: {} and {} paths in {:.3}ms",
+        ladder.small.len(),
+        show(num_ladder_p1),
+        show(num_ladder_p2),
+        before_ladder_time.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let one_revisit = VisitPolicy::new().with_revisits(1, 2);
+    let shortest = caves
+        .paths(one_revisit.clone())
+        .min_by_key(Vec::len)
+        .unwrap();
+    println!("Shortest path: {}", shortest.join(","));
+    println!(
+        "{} paths visiting a small cave up to 3 times, {} visiting two small caves twice",
+        caves.paths(VisitPolicy::new().with_revisits(1, 3)).count(),
+        caves.paths(VisitPolicy::new().with_revisits(2, 2)).count()
+    );
+    let busiest = shortest[1..shortest.len() - 1]
+        .iter()
+        .max_by_key(|name| {
+            caves
+                .paths(one_revisit.clone())
+                .filter(|path| path.contains(name))
+                .count()
+        })
+        .unwrap();
+    let detour = one_revisit.forbid(caves.find_cave(busiest).unwrap());
+    println!(
+        "{} paths avoiding {} (allowing {} visits to {} small cave)",
+        caves.paths(detour.clone()).count(),
+        busiest,
+        detour.max_visits(),
+        detour.revisits()
+    );
+}
+
+fn build(input: &str) -> CaveSystem {
+    let mut builder = CaveSystemBuilder::new();
+    for line in input.lines() {
+        let (a, b) = line.split_once('-').unwrap();
+        builder.add_connection(a, b);
+    }
+    builder.build()
+}
+
+// A line of small caves, where each step can go either directly or through a
+// big cave, so there are 2^steps simple paths
+fn ladder(steps: usize) -> String {
+    let mut input = String::from("start-s0\n");
+    for i in 0..steps {
+        input += &format!("s{0}-s{1}\ns{0}-B{0}\nB{0}-s{1}\n", i, i + 1);
+    }
+    input + &format!("s{}-end\n", steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enumerates_paths() {
+        let caves = build(include_str!("test1.txt"));
+        let mut paths: Vec<String> = caves
+            .paths(VisitPolicy::new())
+            .map(|path| path.join(","))
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "start,A,b,A,c,A,end",
+                "start,A,b,A,end",
+                "start,A,b,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,A,end",
+                "start,b,A,c,A,end",
+                "start,b,A,end",
+                "start,b,end",
+            ]
+        );
+
+        for input in [include_str!("test1.txt"), include_str!("test3.txt")] {
+            let caves = build(input);
+            let (p1, p2) = caves.count_paths();
+            assert_eq!(Some(caves.paths(VisitPolicy::new()).count() as u128), p1);
+            let one_revisit = VisitPolicy::new().with_revisits(1, 2);
+            assert_eq!(Some(caves.paths(one_revisit).count() as u128), p2);
+        }
+    }
+
+    #[test]
+    fn counts_large_systems() {
+        let caves = build(include_str!("test1.txt"));
+        assert_eq!(caves.count_paths(), (Some(10), Some(36)));
+        let caves = build(include_str!("test3.txt"));
+        assert_eq!(caves.count_paths(), (Some(226), Some(3509)));
+
+        for steps in 0..6 {
+            let caves = build(&ladder(steps));
+            let (p1, p2) = caves.count_paths();
+            assert_eq!(p1, Some(1 << steps));
+            let one_revisit = VisitPolicy::new().with_revisits(1, 2);
+            assert_eq!(Some(caves.paths(one_revisit).count() as u128), p2);
+        }
+        let caves = build(&ladder(120));
+        assert_eq!(caves.small.len(), 121);
+        assert_eq!(caves.count_paths().0, Some(1 << 120));
+        assert_eq!(build(&ladder(127)).count_paths(), (Some(1 << 127), None));
+        assert_eq!(build(&ladder(128)).count_paths(), (None, None));
+    }
+
+    #[test]
+    fn follows_policy() {
+        let caves = build(include_str!("test1.txt"));
+        let b = caves.find_cave("b").unwrap();
+        assert_eq!(caves.name(Some(b)), "b");
+        assert_eq!(caves.find_cave("start"), None);
+
+        let avoid_b = VisitPolicy::new().forbid(b);
+        let paths: Vec<_> = caves.paths(avoid_b).collect();
+        assert_eq!(
+            paths,
+            [
+                vec!["start", "A", "c", "A", "end"],
+                vec!["start", "A", "end"]
+            ]
+        );
+        let no_end = VisitPolicy::new().forbid(Cave::End);
+        assert_eq!(caves.paths(no_end).count(), 0);
+
+        // Revisiting c three times, or b and c twice each
+        let three = VisitPolicy::new().with_revisits(1, 3);
+        assert!(caves
+            .paths(three)
+            .any(|p| p.iter().filter(|&&c| c == "c").count() == 3));
+        let two_twice = VisitPolicy::new().with_revisits(2, 2);
+        let two_twice = caves.paths(two_twice).filter(|p| {
+            ["b", "c"]
+                .iter()
+                .all(|name| p.iter().filter(|&c| c == name).count() == 2)
+        });
+        assert!(two_twice.count() > 0);
+        assert_eq!(
+            caves.paths(VisitPolicy::new().with_revisits(5, 1)).count(),
+            10
+        );
+    }
 }
@@ -0,0 +1,139 @@
+use super::{Cave, CaveSystem};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct VisitPolicy {
+    max_visits: u32,
+    revisits: u32,
+    forbidden: Vec<Cave>,
+}
+
+impl VisitPolicy {
+    // Each small cave can be visited once
+    pub const fn new() -> Self {
+        VisitPolicy {
+            max_visits: 1,
+            revisits: 0,
+            forbidden: Vec::new(),
+        }
+    }
+
+    // Up to `revisits` small caves can each be visited up to `max_visits`
+    // times
+    pub fn with_revisits(self, revisits: u32, max_visits: u32) -> Self {
+        assert!(max_visits > 0, "small caves must be visitable");
+        VisitPolicy {
+            max_visits,
+            revisits,
+            ..self
+        }
+    }
+
+    pub fn forbid(mut self, cave: Cave) -> Self {
+        self.forbidden.push(cave);
+        self
+    }
+
+    pub const fn max_visits(&self) -> u32 {
+        self.max_visits
+    }
+
+    pub const fn revisits(&self) -> u32 {
+        self.revisits
+    }
+
+    pub fn is_forbidden(&self, cave: Cave) -> bool {
+        self.forbidden.contains(&cave)
+    }
+
+    // Whether a small cave already visited `visits` times can be visited
+    // again, with `revisits_used` other small caves already revisited
+    fn allows_visit(&self, visits: u32, revisits_used: u32) -> bool {
+        match visits {
+            0 => true,
+            v if v >= self.max_visits => false,
+            1 => revisits_used < self.revisits,
+            _ => true,
+        }
+    }
+}
+
+impl Default for VisitPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Paths<'a> {
+    caves: &'a CaveSystem,
+    policy: VisitPolicy,
+    // Each cave on the current path, with the next connection to try from
+    // it. The start is None.
+    stack: Vec<(Option<Cave>, usize)>,
+    visits: Vec<u32>,
+    revisits_used: u32,
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (cave, next) = self.stack.last_mut()?;
+            let dests = self.caves.connections(*cave);
+            let dest = match dests.get(*next) {
+                Some(&dest) => dest,
+                None => {
+                    if let Some(Cave::Small(index)) = *cave {
+                        self.visits[index] -= 1;
+                        if self.visits[index] == 1 {
+                            self.revisits_used -= 1;
+                        }
+                    }
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            *next += 1;
+            if self.policy.is_forbidden(dest) {
+                continue;
+            }
+            match dest {
+                Cave::End => {
+                    let mut path: Vec<&str> = self
+                        .stack
+                        .iter()
+                        .map(|&(cave, _)| self.caves.name(cave))
+                        .collect();
+                    path.push(self.caves.name(Some(Cave::End)));
+                    return Some(path);
+                }
+                Cave::Big(_) => self.stack.push((Some(dest), 0)),
+                Cave::Small(index) => {
+                    let visits = self.visits[index];
+                    if !self.policy.allows_visit(visits, self.revisits_used) {
+                        continue;
+                    }
+                    if visits == 1 {
+                        self.revisits_used += 1;
+                    }
+                    self.visits[index] += 1;
+                    self.stack.push((Some(dest), 0));
+                }
+            }
+        }
+    }
+}
+
+impl CaveSystem {
+    // Connected big caves would allow endless paths, so there mustn't be any
+    pub fn paths(&self, policy: VisitPolicy) -> Paths<'_> {
+        Paths {
+            caves: self,
+            stack: vec![(None, 0)],
+            policy,
+            visits: vec![0; self.small.len()],
+            revisits_used: 0,
+        }
+    }
+}